@@ -0,0 +1,131 @@
+//! Compares tile lookups through the WorldGrid index with the previous approach of searching
+//! every chunk tilemap for the tile at a position.
+//!
+//! Run with `cargo bench --bench world_grid`.
+use std::{hint::black_box, time::Instant};
+
+use bevy::{ecs::system::SystemState, prelude::*};
+use bevy_ecs_tilemap::prelude::*;
+use m_rouge::game::{
+    map::{CHUNK_SIZE, GameGrid, GridPos, TILE_SIZE},
+    tiles::TileKind,
+    world_grid::{WorldGrid, WorldTile},
+};
+
+/// same amount of chunks the game keeps spawned around the player
+const CHUNKS: i32 = 4;
+/// same amount of lookups a FieldOfView with a view range of 10 does per update
+const VIEW_RANGE: i32 = 10;
+const ITERATIONS: u32 = 100;
+
+type ChunksQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static TileStorage,
+        &'static TilemapSize,
+        &'static TilemapGridSize,
+        &'static TilemapType,
+        &'static Transform,
+    ),
+>;
+
+fn main() {
+    let mut world = World::new();
+    let mut world_grid = WorldGrid::default();
+
+    for chunk_x in 0..CHUNKS {
+        for chunk_y in 0..CHUNKS {
+            let chunk_pos = IVec2::new(chunk_x, chunk_y);
+            let chunk = world.spawn_empty().id();
+            let mut storage = TileStorage::empty(CHUNK_SIZE.into());
+
+            for x in 0..CHUNK_SIZE.x {
+                for y in 0..CHUNK_SIZE.y {
+                    let tile_pos = TilePos { x, y };
+                    let kind = TileKind(((x + y) % 2) as u16);
+                    let entity = world.spawn((tile_pos, kind)).id();
+                    storage.set(&tile_pos, entity);
+                    world_grid.insert(
+                        GameGrid::tile_pos_to_grid_pos(chunk_pos, tile_pos),
+                        WorldTile {
+                            chunk,
+                            tile_pos,
+                            entity,
+                            kind,
+                        },
+                    );
+                }
+            }
+
+            world.entity_mut(chunk).insert((
+                storage,
+                TilemapSize::from(CHUNK_SIZE),
+                TilemapGridSize::from(TILE_SIZE),
+                TilemapType::Square,
+                Transform::from_translation(GameGrid::chunk_pos_to_world_pos(chunk_pos)),
+            ));
+        }
+    }
+
+    let center = GridPos {
+        x: CHUNKS * CHUNK_SIZE.x as i32 / 2,
+        y: CHUNKS * CHUNK_SIZE.y as i32 / 2,
+    };
+    let positions: Vec<GridPos> = (-VIEW_RANGE..=VIEW_RANGE)
+        .flat_map(|y| {
+            (-VIEW_RANGE..=VIEW_RANGE).map(move |x| GridPos {
+                x: center.x + x,
+                y: center.y + y,
+            })
+        })
+        .collect();
+
+    let mut state: SystemState<(ChunksQuery, Query<&TileKind>)> = SystemState::new(&mut world);
+    let (chunks_query, tile_query) = state.get(&world);
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        for pos in &positions {
+            black_box(chunk_scan_lookup(pos, &chunks_query, &tile_query));
+        }
+    }
+    let chunk_scan = start.elapsed() / ITERATIONS;
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        for pos in &positions {
+            black_box(world_grid.kind(pos));
+        }
+    }
+    let indexed = start.elapsed() / ITERATIONS;
+
+    println!(
+        "{} lookups over {} chunks",
+        positions.len(),
+        CHUNKS * CHUNKS
+    );
+    println!("chunk scan: {chunk_scan:?}");
+    println!("world grid: {indexed:?}");
+    println!(
+        "speedup:    {:.1}x",
+        chunk_scan.as_secs_f64() / indexed.as_secs_f64()
+    );
+}
+
+/// The lookup GameGrid used before the WorldGrid existed
+fn chunk_scan_lookup(
+    pos: &GridPos,
+    chunks_query: &ChunksQuery,
+    tile_query: &Query<&TileKind>,
+) -> Option<TileKind> {
+    let world_pos = pos.to_world_pos();
+    chunks_query
+        .iter()
+        .find_map(|(tile_storage, map_size, grid_size, map_type, transform)| {
+            let pos_in_chunk =
+                (transform.compute_matrix().inverse() * Vec4::from((world_pos, 0.0, 1.0))).xy();
+            let tile_pos = TilePos::from_world_pos(&pos_in_chunk, map_size, grid_size, map_type)?;
+            tile_query.get(tile_storage.get(&tile_pos)?).ok().copied()
+        })
+}
@@ -0,0 +1,68 @@
+//! Plays the game without a window, moving the player in random directions.
+//! Useful for balance runs and to check that nothing breaks over many turns.
+//! Stops early when the player dies.
+//!
+//! `cargo run --example simulation -- [turns] [seed]`
+use bevy::prelude::*;
+use m_rouge::{
+    SimulationPlugin,
+    game::{combat::PlayerDied, map::WorldSeed, player::PlayerAction},
+    states::TurnState,
+};
+use rand::seq::IndexedRandom;
+
+#[derive(Resource)]
+struct Turns {
+    played: u32,
+    limit: u32,
+}
+
+fn main() -> AppExit {
+    let mut args = std::env::args().skip(1);
+    let limit = args.next().and_then(|arg| arg.parse().ok()).unwrap_or(1000);
+    let seed = args.next().and_then(|arg| arg.parse().ok());
+
+    let mut app = App::new();
+    if let Some(seed) = seed {
+        app.insert_resource(WorldSeed(seed));
+    }
+
+    app.add_plugins(SimulationPlugin)
+        .insert_resource(Turns { played: 0, limit })
+        .add_systems(
+            Update,
+            random_player_action.run_if(in_state(TurnState::Player)),
+        )
+        .add_systems(OnEnter(TurnState::Player), count_turn)
+        .add_systems(Update, exit_on_death);
+
+    app.run()
+}
+
+fn random_player_action(mut actions: EventWriter<PlayerAction>) {
+    let directions = [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y];
+    if let Some(direction) = directions.choose(&mut rand::rng()) {
+        actions.send(PlayerAction::Move(*direction));
+    }
+}
+
+fn count_turn(mut turns: ResMut<Turns>, seed: Res<WorldSeed>, mut exit: EventWriter<AppExit>) {
+    turns.played += 1;
+
+    if turns.played >= turns.limit {
+        info!("played {} turns with seed {}", turns.played, seed.0);
+        exit.send(AppExit::Success);
+    }
+}
+
+fn exit_on_death(
+    mut deaths: EventReader<PlayerDied>,
+    turns: Res<Turns>,
+    seed: Res<WorldSeed>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if deaths.read().next().is_some() {
+        info!("died after {} turns with seed {}", turns.played, seed.0);
+        exit.send(AppExit::Success);
+    }
+}
@@ -5,16 +5,26 @@ use bevy::{
         states::log_transitions,
         ui_debug_overlay::{DebugUiPlugin, UiDebugOptions},
     },
-    input::common_conditions::input_just_pressed,
     prelude::*,
 };
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
-use crate::states::{Screen, TurnState};
+use crate::{
+    components::Player,
+    game::{
+        input::{Action, ActionState},
+        map::{GameGrid, GridMovement},
+        map_generator::isolated_regions,
+        tiles::TileRegistry,
+        world_grid::WorldGrid,
+    },
+    states::{Pause, Screen, TurnState},
+};
 
 pub(super) fn plugin(app: &mut App) {
     // Log `Screen` state transitions.
     app.add_systems(Update, log_transitions::<Screen>);
+    app.add_systems(Update, log_transitions::<Pause>);
     app.add_systems(Update, log_transitions::<TurnState>);
 
     // bevy_inspector_egui
@@ -35,14 +45,49 @@ pub(super) fn plugin(app: &mut App) {
 
     // Toggle the debug overlay for UI.
     app.add_plugins(DebugUiPlugin);
+    app.add_systems(Update, toggle_debug_ui);
+
+    // Check that map generation never cuts off parts of the loaded world.
     app.add_systems(
         Update,
-        toggle_debug_ui.run_if(input_just_pressed(TOGGLE_KEY)),
+        validate_connectivity
+            .run_if(in_state(Screen::Gameplay))
+            .run_if(resource_exists::<TileRegistry>),
     );
 }
 
-const TOGGLE_KEY: KeyCode = KeyCode::F10;
+fn toggle_debug_ui(action_state: Res<ActionState>, mut options: ResMut<UiDebugOptions>) {
+    if action_state.just_pressed(Action::ToggleDebug) {
+        options.toggle();
+    }
+}
+
+/// Flood fills the loaded chunks from the player whenever the WorldGrid changes and reports
+/// every walkable region the player can't reach.
+fn validate_connectivity(
+    world_grid: Res<WorldGrid>,
+    tiles: Res<TileRegistry>,
+    player_query: Query<&GridMovement, With<Player>>,
+    mut validated_revision: Local<Option<u64>>,
+) {
+    let Ok(grid_movement) = player_query.get_single() else {
+        return;
+    };
+    let player_pos = grid_movement.current_pos;
+    // wait until the player's chunk is spawned
+    if world_grid.get(&player_pos).is_none() || *validated_revision == Some(world_grid.revision()) {
+        return;
+    }
+    *validated_revision = Some(world_grid.revision());
 
-fn toggle_debug_ui(mut options: ResMut<UiDebugOptions>) {
-    options.toggle();
+    if !GameGrid::is_walkable(&player_pos, &world_grid, &tiles) {
+        warn!("the player stands on an unwalkable tile at {player_pos:?}");
+    }
+    for region in isolated_regions(&world_grid, &tiles, player_pos) {
+        warn!(
+            "isolated region of {} tiles at {:?} can't be reached from the player",
+            region.len(),
+            region[0]
+        );
+    }
 }
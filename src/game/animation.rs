@@ -30,6 +30,16 @@ impl AnimationConfig {
         };
         Timer::new(duration, mode)
     }
+
+    /// Whether a non looping animation has shown its last frame for a full frame duration
+    pub fn is_finished(&self, sprite: &Sprite) -> bool {
+        !self.should_loop
+            && self.frame_timer.finished()
+            && sprite
+                .texture_atlas
+                .as_ref()
+                .is_some_and(|atlas| atlas.index == self.last_sprite_index)
+    }
 }
 
 pub(super) fn plugin(app: &mut App) {
@@ -45,18 +55,21 @@ fn execute_animations(time: Res<Time>, mut query: Query<(&mut AnimationConfig, &
 
         // If it has been displayed for the user-defined amount of time (fps)...
         // this only happens for should_loop == false AnimationConfig's
-        if config.frame_timer.just_finished() {
-            if let Some(atlas) = &mut sprite.texture_atlas {
-                if atlas.index == config.last_sprite_index {
-                    // ...and it IS the last frame, then we move back to the first frame and stop.
+        if config.frame_timer.just_finished()
+            && let Some(atlas) = &mut sprite.texture_atlas
+        {
+            if atlas.index == config.last_sprite_index {
+                // ...and it IS the last frame, then looping animations move back to the first
+                // frame, the others stop on the last one.
+                if config.should_loop {
                     atlas.index = config.first_sprite_index;
-                } else {
-                    // ...and it is NOT the last frame, then we move to the next frame...
-                    atlas.index += 1;
-                    // ...and reset the frame timer to start counting all over again
-                    config.frame_timer =
-                        AnimationConfig::timer_from_fps(config.fps, config.should_loop);
                 }
+            } else {
+                // ...and it is NOT the last frame, then we move to the next frame...
+                atlas.index += 1;
+                // ...and reset the frame timer to start counting all over again
+                config.frame_timer =
+                    AnimationConfig::timer_from_fps(config.fps, config.should_loop);
             }
         }
     }
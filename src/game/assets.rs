@@ -0,0 +1,90 @@
+use bevy::{
+    asset::{LoadState, UntypedAssetId},
+    prelude::*,
+};
+
+use super::{
+    input::{INPUT_BINDINGS_PATH, InputBindings},
+    tiles::{TILE_DEFINITIONS_PATH, TileDefinitions},
+};
+
+const ATLAS_PATH: &str = "images/atlas.png";
+const DEVIL_PATH: &str = "images/devil.png";
+const PLAYER_IDLE_PATH: &str = "images/player/idle/idle.png";
+const PLAYER_DEATH_PATH: &str = "images/player/death/death.png";
+
+/// Handles of every asset the game needs to render a run.
+/// Loading starts right away, the Loading screen waits until everything finished loading.
+/// Spawning code clones these handles instead of asking the AssetServer again.
+#[derive(Resource, Debug, Clone)]
+pub struct GameAssets {
+    /// tile sprites, indexed by `TileDefinition::atlas_index`
+    pub atlas: Handle<Image>,
+    pub devil: Handle<Image>,
+    pub player_idle: Handle<Image>,
+    pub player_death: Handle<Image>,
+    /// frames of the player animations, shared by all of them
+    pub player_layout: Handle<TextureAtlasLayout>,
+    /// the TileRegistry is built from these once they are loaded
+    pub tile_definitions: Handle<TileDefinitions>,
+    pub input_bindings: Handle<InputBindings>,
+}
+
+impl GameAssets {
+    /// The loaded assets, with the path they are loaded from
+    fn required(&self) -> [(&'static str, UntypedAssetId); 6] {
+        [
+            (ATLAS_PATH, self.atlas.id().untyped()),
+            (DEVIL_PATH, self.devil.id().untyped()),
+            (PLAYER_IDLE_PATH, self.player_idle.id().untyped()),
+            (PLAYER_DEATH_PATH, self.player_death.id().untyped()),
+            (TILE_DEFINITIONS_PATH, self.tile_definitions.id().untyped()),
+            (INPUT_BINDINGS_PATH, self.input_bindings.id().untyped()),
+        ]
+    }
+
+    /// How many of the assets finished loading, and how many there are.
+    /// Panics if any of them failed to load, the game can't run without them.
+    pub fn progress(&self, asset_server: &AssetServer) -> (usize, usize) {
+        let required = self.required();
+        let mut loaded = 0;
+
+        for (path, id) in required {
+            if let LoadState::Failed(err) = asset_server.load_state(id) {
+                panic!("required asset {path} could not be loaded: {err}");
+            }
+            if asset_server.is_loaded_with_dependencies(id) {
+                loaded += 1;
+            }
+        }
+
+        (loaded, required.len())
+    }
+
+    pub fn is_loaded(&self, asset_server: &AssetServer) -> bool {
+        let (loaded, total) = self.progress(asset_server);
+        loaded == total
+    }
+}
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(Startup, load_game_assets);
+}
+
+fn load_game_assets(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
+    let player_layout = TextureAtlasLayout::from_grid(UVec2::new(48, 64), 8, 1, None, None);
+
+    commands.insert_resource(GameAssets {
+        atlas: asset_server.load(ATLAS_PATH),
+        devil: asset_server.load(DEVIL_PATH),
+        player_idle: asset_server.load(PLAYER_IDLE_PATH),
+        player_death: asset_server.load(PLAYER_DEATH_PATH),
+        player_layout: texture_atlas_layouts.add(player_layout),
+        tile_definitions: asset_server.load(TILE_DEFINITIONS_PATH),
+        input_bindings: asset_server.load(INPUT_BINDINGS_PATH),
+    });
+}
@@ -2,13 +2,28 @@ use bevy::prelude::*;
 
 use crate::components::Player;
 
+use super::{
+    input::{Action, ActionState},
+    map::GridMovement,
+    save::GameLoaded,
+};
+
 #[derive(Component)]
 pub struct FollowedByCamera;
 
 pub(super) fn plugin(app: &mut App) {
     // Spawn the main camera.
     app.add_systems(Startup, spawn_camera);
-    app.add_systems(Update, (movement, follow_player));
+    app.add_systems(
+        Update,
+        (
+            movement,
+            follow_moving_player,
+            follow_loaded_player,
+            follow_new_player,
+            follow_player,
+        ),
+    );
 }
 
 fn spawn_camera(mut commands: Commands) {
@@ -30,7 +45,7 @@ fn spawn_camera(mut commands: Commands) {
 fn movement(
     mut commands: Commands,
     time: Res<Time>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
+    action_state: Res<ActionState>,
     mut query: Query<(&mut Transform, &mut OrthographicProjection), With<IsDefaultUiCamera>>,
     player_query: Query<Entity, (With<Player>, Without<IsDefaultUiCamera>)>,
 ) {
@@ -42,36 +57,44 @@ fn movement(
         let mut direction = Vec3::ZERO;
 
         // jump to the player position
-        if keyboard_input.just_pressed(KeyCode::Space) {
+        if action_state.just_pressed(Action::CenterCamera) {
             commands.entity(player_entity).insert(FollowedByCamera);
             return;
         }
 
-        if keyboard_input.pressed(KeyCode::ArrowLeft) {
+        if action_state.pressed(Action::PanLeft) {
             direction.x -= 1.0;
             commands.entity(player_entity).remove::<FollowedByCamera>();
         }
 
-        if keyboard_input.pressed(KeyCode::ArrowRight) {
+        if action_state.pressed(Action::PanRight) {
             direction.x += 1.0;
             commands.entity(player_entity).remove::<FollowedByCamera>();
         }
 
-        if keyboard_input.pressed(KeyCode::ArrowUp) {
+        if action_state.pressed(Action::PanUp) {
             direction.y += 1.0;
             commands.entity(player_entity).remove::<FollowedByCamera>();
         }
 
-        if keyboard_input.pressed(KeyCode::ArrowDown) {
+        if action_state.pressed(Action::PanDown) {
             direction.y -= 1.0;
             commands.entity(player_entity).remove::<FollowedByCamera>();
         }
 
-        if keyboard_input.pressed(KeyCode::PageUp) {
+        // right stick and triggers
+        let pan = action_state.camera_pan();
+        if pan != Vec2::ZERO {
+            direction += pan.extend(0.0);
+            commands.entity(player_entity).remove::<FollowedByCamera>();
+        }
+        ortho.scale -= action_state.camera_zoom() * time.delta_secs() * 2.0;
+
+        if action_state.pressed(Action::ZoomOut) {
             ortho.scale += 0.1;
         }
 
-        if keyboard_input.pressed(KeyCode::PageDown) {
+        if action_state.pressed(Action::ZoomIn) {
             ortho.scale -= 0.1;
         }
 
@@ -84,6 +107,40 @@ fn movement(
     }
 }
 
+/// start following the player again once it moves
+fn follow_moving_player(
+    mut commands: Commands,
+    player_query: Query<(Entity, &GridMovement), (With<Player>, Changed<GridMovement>)>,
+) {
+    for (entity, movement) in player_query.iter() {
+        if movement.target_pos.is_some() {
+            commands.entity(entity).insert(FollowedByCamera);
+        }
+    }
+}
+
+/// the player may be far away from the camera after loading a save
+fn follow_loaded_player(
+    mut commands: Commands,
+    mut loaded: EventReader<GameLoaded>,
+    player_query: Query<Entity, With<Player>>,
+) {
+    if loaded.read().count() == 0 {
+        return;
+    }
+
+    for entity in player_query.iter() {
+        commands.entity(entity).insert(FollowedByCamera);
+    }
+}
+
+/// a new run starts wherever the camera was left by the last one
+fn follow_new_player(mut commands: Commands, player_query: Query<Entity, Added<Player>>) {
+    for entity in player_query.iter() {
+        commands.entity(entity).insert(FollowedByCamera);
+    }
+}
+
 fn follow_player(
     time: Res<Time>,
     mut camera_query: Query<&mut Transform, With<IsDefaultUiCamera>>,
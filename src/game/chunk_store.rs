@@ -0,0 +1,317 @@
+use std::{collections::VecDeque, fs, path::PathBuf, process};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::components::TurnTaker;
+
+use super::{
+    combat::Health,
+    map::{CHUNK_SIZE, ChunkLayout, GridPos, WorldSeed},
+    tiles::TileKind,
+};
+
+/// Keeps track of which chunks are spawned and stores the state of chunks that left the spawn
+/// range, so they can be restored exactly as they were once the player comes back.
+#[derive(Debug, Resource)]
+pub struct ChunkManager {
+    pub spawned_chunks: HashSet<IVec2>,
+    /// max amount of stored chunks kept in memory
+    pub memory_budget: usize,
+    /// chunks over the memory budget are written to a directory for the current run in here.
+    /// If None, the budget is ignored and every stored chunk stays in memory.
+    pub spill_dir: Option<PathBuf>,
+    /// directory of the current run in spill_dir, set by start_run.
+    /// Named after the process and seed, so parallel runs never read each other's chunks.
+    run_dir: Option<PathBuf>,
+    stored_chunks: HashMap<IVec2, StoredChunk>,
+    /// stored chunk positions, least recently stored first
+    stored_order: VecDeque<IVec2>,
+    /// positions of the chunks written to the spill directory
+    spilled_chunks: HashSet<IVec2>,
+}
+
+impl Default for ChunkManager {
+    fn default() -> Self {
+        Self {
+            spawned_chunks: HashSet::new(),
+            memory_budget: 64,
+            spill_dir: Some(std::env::temp_dir().join("m-rouge-chunks")),
+            run_dir: None,
+            stored_chunks: HashMap::new(),
+            stored_order: VecDeque::new(),
+            spilled_chunks: HashSet::new(),
+        }
+    }
+}
+
+impl ChunkManager {
+    /// Store a chunk that left the spawn range.
+    /// Spills the least recently stored chunks to disk once the memory budget is exceeded.
+    /// Chunks are never dropped, if they can't be spilled they stay in memory.
+    pub fn store(&mut self, chunk_pos: IVec2, chunk: StoredChunk) {
+        self.stored_order.retain(|pos| *pos != chunk_pos);
+        self.stored_order.push_back(chunk_pos);
+        self.stored_chunks.insert(chunk_pos, chunk);
+
+        if self.run_dir.is_none() {
+            return;
+        }
+        while self.stored_chunks.len() > self.memory_budget {
+            let Some(evicted_pos) = self.stored_order.pop_front() else {
+                break;
+            };
+            let Some(evicted) = self.stored_chunks.remove(&evicted_pos) else {
+                continue;
+            };
+            if !self.spill(evicted_pos, &evicted) {
+                // keep it in memory instead of losing it, the next store tries again
+                self.stored_chunks.insert(evicted_pos, evicted);
+                self.stored_order.push_front(evicted_pos);
+                break;
+            }
+        }
+    }
+
+    /// Put a chunk back into memory without checking the memory budget, e.g. when loading a save.
+    /// The next call to store spills the chunks over the budget.
+    pub fn restore(&mut self, chunk_pos: IVec2, chunk: StoredChunk) {
+        self.stored_order.retain(|pos| *pos != chunk_pos);
+        self.stored_order.push_back(chunk_pos);
+        self.stored_chunks.insert(chunk_pos, chunk);
+    }
+
+    /// Take a previously stored chunk out of the store, looking in memory first and then on disk.
+    pub fn take(&mut self, chunk_pos: IVec2) -> Option<StoredChunk> {
+        if let Some(chunk) = self.stored_chunks.remove(&chunk_pos) {
+            self.stored_order.retain(|pos| *pos != chunk_pos);
+            return Some(chunk);
+        }
+
+        if !self.spilled_chunks.remove(&chunk_pos) {
+            return None;
+        }
+        let chunk = self.read_spilled(chunk_pos)?;
+        self.remove_spilled(chunk_pos);
+        Some(chunk)
+    }
+
+    /// Every stored chunk, in memory and on disk, without taking them out of the store
+    pub fn stored_chunks(&self) -> Vec<(IVec2, StoredChunk)> {
+        let in_memory = self
+            .stored_chunks
+            .iter()
+            .map(|(chunk_pos, chunk)| (*chunk_pos, chunk.clone()));
+        let on_disk = self
+            .spilled_chunks
+            .iter()
+            .filter_map(|chunk_pos| Some((*chunk_pos, self.read_spilled(*chunk_pos)?)));
+
+        in_memory.chain(on_disk).collect()
+    }
+
+    /// Forget every stored chunk and spill the chunks of the run with `seed` to a new directory
+    pub fn start_run(&mut self, seed: &WorldSeed) {
+        self.clear();
+        self.run_dir = self
+            .spill_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}-{}", process::id(), seed.0)));
+        // left over from an earlier process with the same id
+        self.remove_run_dir();
+    }
+
+    /// Forget every stored chunk, including the ones on disk.
+    /// Spawned chunks have to be despawned separately.
+    pub fn clear(&mut self) {
+        self.remove_run_dir();
+        self.spilled_chunks.clear();
+        self.spawned_chunks.clear();
+        self.stored_chunks.clear();
+        self.stored_order.clear();
+    }
+
+    fn read_spilled(&self, chunk_pos: IVec2) -> Option<StoredChunk> {
+        let path = self.spill_path(chunk_pos)?;
+        let content = fs::read_to_string(&path).ok()?;
+
+        match ron::from_str(&content) {
+            Ok(chunk) => Some(chunk),
+            Err(err) => {
+                error!("could not read spilled chunk {}: {err}", path.display());
+                None
+            }
+        }
+    }
+
+    fn remove_spilled(&self, chunk_pos: IVec2) {
+        let Some(path) = self.spill_path(chunk_pos) else {
+            return;
+        };
+        if let Err(err) = fs::remove_file(&path) {
+            warn!("could not remove spilled chunk {}: {err}", path.display());
+        }
+    }
+
+    /// Write a chunk to the spill directory, returns whether it was written
+    fn spill(&mut self, chunk_pos: IVec2, chunk: &StoredChunk) -> bool {
+        let Some(path) = self.spill_path(chunk_pos) else {
+            return false;
+        };
+
+        let result = ron::to_string(chunk)
+            .map_err(|err| err.to_string())
+            .and_then(|content| {
+                fs::create_dir_all(path.parent().unwrap_or(&path))
+                    .and_then(|_| fs::write(&path, content))
+                    .map_err(|err| err.to_string())
+            });
+
+        match result {
+            Ok(()) => {
+                self.spilled_chunks.insert(chunk_pos);
+                true
+            }
+            Err(err) => {
+                error!("could not spill chunk to {}: {err}", path.display());
+                false
+            }
+        }
+    }
+
+    fn remove_run_dir(&self) {
+        let Some(run_dir) = &self.run_dir else {
+            return;
+        };
+        if run_dir.exists()
+            && let Err(err) = fs::remove_dir_all(run_dir)
+        {
+            warn!(
+                "could not remove spilled chunks {}: {err}",
+                run_dir.display()
+            );
+        }
+    }
+
+    fn spill_path(&self, chunk_pos: IVec2) -> Option<PathBuf> {
+        self.run_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("chunk_{}_{}.ron", chunk_pos.x, chunk_pos.y)))
+    }
+}
+
+/// Everything needed to respawn a chunk in the state it was left in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredChunk {
+    /// kind of each tile, indexed by `y * CHUNK_SIZE.x + x`
+    pub tiles: Vec<TileKind>,
+    /// actors that were inside the chunk when it was despawned
+    pub actors: Vec<StoredActor>,
+}
+
+impl StoredChunk {
+    pub fn tile(&self, x: u32, y: u32) -> TileKind {
+        self.tiles[(y * CHUNK_SIZE.x + x) as usize]
+    }
+}
+
+impl From<ChunkLayout> for StoredChunk {
+    fn from(layout: ChunkLayout) -> Self {
+        Self {
+            tiles: layout.tiles,
+            actors: layout
+                .devils
+                .into_iter()
+                .map(|pos| StoredActor {
+                    kind: ActorKind::Devil,
+                    pos,
+                    turn_taker: TurnTaker::default(),
+                    health: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredActor {
+    pub kind: ActorKind,
+    pub pos: GridPos,
+    pub turn_taker: TurnTaker,
+    /// None for actors that were never stored, they spawn with their default health
+    #[serde(default)]
+    pub health: Option<Health>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ActorKind {
+    Devil,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(kind: u16) -> StoredChunk {
+        StoredChunk {
+            tiles: vec![TileKind(kind); (CHUNK_SIZE.x * CHUNK_SIZE.y) as usize],
+            actors: Vec::new(),
+        }
+    }
+
+    const POSITIONS: usize = 10;
+
+    fn store_chunks(chunk_manager: &mut ChunkManager) {
+        for index in 0..POSITIONS {
+            let pos = IVec2::new(index as i32, -(index as i32));
+            chunk_manager.store(pos, chunk(index as u16));
+        }
+    }
+
+    fn take_chunks_back(chunk_manager: &mut ChunkManager) {
+        for index in 0..POSITIONS {
+            let pos = IVec2::new(index as i32, -(index as i32));
+            let chunk = chunk_manager.take(pos).expect("stored chunk was lost");
+            assert_eq!(chunk.tiles[0], TileKind(index as u16));
+        }
+    }
+
+    #[test]
+    fn chunks_over_the_budget_are_spilled() {
+        let spill_dir = std::env::temp_dir().join(format!("m-rouge-spill-{}", process::id()));
+        let mut chunk_manager = ChunkManager {
+            memory_budget: 2,
+            spill_dir: Some(spill_dir.clone()),
+            ..default()
+        };
+        chunk_manager.start_run(&WorldSeed(7));
+        let run_dir = chunk_manager.run_dir.clone().unwrap();
+        assert!(run_dir.starts_with(&spill_dir));
+
+        store_chunks(&mut chunk_manager);
+
+        let spilled = fs::read_dir(&run_dir).unwrap().count();
+        assert_eq!(spilled, POSITIONS - chunk_manager.memory_budget);
+        take_chunks_back(&mut chunk_manager);
+
+        chunk_manager.clear();
+        assert!(!run_dir.exists());
+        let _ = fs::remove_dir_all(spill_dir);
+    }
+
+    #[test]
+    fn chunks_are_kept_without_spill_dir() {
+        let mut chunk_manager = ChunkManager {
+            memory_budget: 2,
+            spill_dir: None,
+            ..default()
+        };
+        chunk_manager.start_run(&WorldSeed(7));
+
+        store_chunks(&mut chunk_manager);
+        take_chunks_back(&mut chunk_manager);
+    }
+}
@@ -0,0 +1,113 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    game_log::{LogKind, LogMessage},
+    player::Player,
+};
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Reflect, Serialize, Deserialize)]
+#[reflect(Component)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current == 0
+    }
+}
+
+/// Damage dealt by a melee attack, before the target's Defense is subtracted
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+pub struct Attack(pub u32);
+
+/// Damage taken away from every melee attack that hits this entity
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+pub struct Defense(pub u32);
+
+/// Added to the player when its health drops to zero.
+/// It can't act anymore, the presentation plays the death animation and ends the game.
+#[derive(Component, Debug)]
+pub struct Dead;
+
+/// One entity hitting another one in melee, usually by bumping into it.
+/// The action is already paid for by the attacker when this is sent.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct AttackEvent {
+    pub attacker: Entity,
+    pub target: Entity,
+}
+
+/// Sent once when the player died
+#[derive(Event, Debug, Clone, Copy)]
+pub struct PlayerDied;
+
+pub(super) fn plugin(app: &mut App) {
+    app.register_type::<Health>()
+        .register_type::<Attack>()
+        .register_type::<Defense>()
+        .add_event::<AttackEvent>()
+        .add_event::<PlayerDied>()
+        .add_systems(PostUpdate, resolve_attacks);
+}
+
+/// Every attack deals at least one damage, no matter how high the target's defense is
+pub fn melee_damage(attack: Option<&Attack>, defense: Option<&Defense>) -> u32 {
+    let attack = attack.map_or(0, |attack| attack.0);
+    let defense = defense.map_or(0, |defense| defense.0);
+    attack.saturating_sub(defense).max(1)
+}
+
+fn resolve_attacks(
+    mut commands: Commands,
+    mut attacks: EventReader<AttackEvent>,
+    attacker_query: Query<(&Name, Option<&Attack>)>,
+    mut target_query: Query<(&Name, &mut Health, Option<&Defense>, Has<Player>)>,
+    mut player_died: EventWriter<PlayerDied>,
+    mut log: EventWriter<LogMessage>,
+) {
+    for attack in attacks.read() {
+        let Ok((attacker_name, attacker_attack)) = attacker_query.get(attack.attacker) else {
+            continue;
+        };
+        let Ok((target_name, mut health, defense, is_player)) = target_query.get_mut(attack.target)
+        else {
+            continue;
+        };
+        if health.is_dead() {
+            continue;
+        }
+
+        let damage = melee_damage(attacker_attack, defense);
+        health.current = health.current.saturating_sub(damage);
+        log.send(LogMessage::new(
+            LogKind::Combat,
+            format!("{attacker_name} hits {target_name} for {damage} damage."),
+        ));
+
+        if !health.is_dead() {
+            continue;
+        }
+
+        let kind = if is_player {
+            LogKind::Danger
+        } else {
+            LogKind::Combat
+        };
+        log.send(LogMessage::new(kind, format!("{target_name} dies.")));
+        if is_player {
+            commands.entity(attack.target).insert(Dead);
+            player_died.send(PlayerDied);
+        } else {
+            commands.entity(attack.target).despawn_recursive();
+        }
+    }
+}
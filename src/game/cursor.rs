@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+
+use crate::states::{Pause, Screen};
+
+use super::{
+    input::{Action, ActionState},
+    map::{GridMovement, GridPos, HoveredTilePos},
+    player::{Player, PlayerAction},
+};
+
+/// What confirming the tile cursor does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CursorMode {
+    /// walk the player to the selected tile
+    #[default]
+    Travel,
+    /// only look at tiles, see the look module for the tooltip
+    Look,
+}
+
+/// Tile selection with the keyboard or a gamepad, as an alternative to the mouse.
+/// While it is active, movement actions move the cursor instead of the player and the cursor
+/// drives the HoveredTilePos.
+#[derive(Resource, Debug, Default)]
+pub struct TileCursor {
+    pub pos: Option<GridPos>,
+    pub mode: CursorMode,
+}
+
+/// Run condition for systems that should ignore input while the cursor is active
+pub fn tile_cursor_inactive(cursor: Res<TileCursor>) -> bool {
+    cursor.pos.is_none()
+}
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<TileCursor>()
+        .add_systems(
+            Update,
+            (toggle_tile_cursor, move_tile_cursor)
+                .chain()
+                .run_if(in_state(Pause::Running)),
+        )
+        .add_systems(OnExit(Screen::Gameplay), hide_tile_cursor);
+}
+
+fn hide_tile_cursor(mut cursor: ResMut<TileCursor>) {
+    cursor.pos = None;
+}
+
+fn toggle_tile_cursor(
+    action_state: Res<ActionState>,
+    mut cursor: ResMut<TileCursor>,
+    mut hovered_tile_pos: ResMut<HoveredTilePos>,
+    player_query: Query<&GridMovement, With<Player>>,
+) {
+    let toggled_mode = if action_state.just_pressed(Action::ToggleCursor) {
+        Some(CursorMode::Travel)
+    } else if action_state.just_pressed(Action::Look) {
+        Some(CursorMode::Look)
+    } else {
+        None
+    };
+
+    if cursor.pos.is_some() && (toggled_mode.is_some() || action_state.just_pressed(Action::Cancel))
+    {
+        cursor.pos = None;
+        hovered_tile_pos.0 = None;
+    } else if cursor.pos.is_none()
+        && let Some(mode) = toggled_mode
+    {
+        cursor.pos = player_query
+            .get_single()
+            .ok()
+            .map(|movement| movement.current_pos);
+        cursor.mode = mode;
+    }
+}
+
+fn move_tile_cursor(
+    action_state: Res<ActionState>,
+    mut cursor: ResMut<TileCursor>,
+    mut hovered_tile_pos: ResMut<HoveredTilePos>,
+    mut actions: EventWriter<PlayerAction>,
+) {
+    let Some(mut pos) = cursor.pos else {
+        return;
+    };
+
+    for direction in Action::MOVEMENT
+        .iter()
+        .filter(|action| action_state.triggered(**action))
+        .filter_map(Action::direction)
+    {
+        pos = pos.offset(direction);
+    }
+
+    if action_state.just_pressed(Action::Confirm) {
+        if cursor.mode == CursorMode::Travel {
+            actions.send(PlayerAction::TravelTo(pos));
+        }
+        cursor.pos = None;
+        hovered_tile_pos.0 = None;
+        return;
+    }
+
+    cursor.pos = Some(pos);
+    hovered_tile_pos.0 = Some(pos.to_world_pos());
+}
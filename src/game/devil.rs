@@ -1,122 +1,416 @@
-use super::map::GameGrid;
+use super::assets::GameAssets;
+use super::combat::{Attack, AttackEvent, Dead, Defense, Health};
+use super::game_log::{LogKind, LogMessage};
+use super::map::{GameGrid, GameRng, MovementTopology, PathBudget};
+use super::occupancy::Occupancy;
+use super::path_cache::PathCache;
+use super::player::Player;
+use super::tiles::TileRegistry;
+use super::turns::{ACTION_COST, TurnQueue, move_cost};
+use super::world_grid::WorldGrid;
 use crate::{
-    components::TurnTaker,
+    components::{FieldOfView, TurnTaker},
     game::map::{GridMovement, GridPos, TILE_SIZE},
-    states::TurnState,
+    states::{Pause, Screen, TurnState},
 };
-use bevy::prelude::*;
-use bevy_ecs_tilemap::prelude::*;
+use bevy::{prelude::*, utils::HashSet};
 use rand::seq::SliceRandom;
 
 #[derive(Component)]
-struct Devil;
+pub struct Devil;
+
+/// What a devil is currently up to.
+/// Wandering -> Noticed -> Chasing -> LostTrack -> Wandering
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default, Reflect)]
+#[reflect(Component)]
+pub enum DevilState {
+    /// walks around randomly until it sees the player
+    #[default]
+    Wandering,
+    /// just saw the player and spends its next action reacting to it
+    Noticed { player_pos: GridPos },
+    /// follows the player while it can see it
+    Chasing { last_seen: GridPos },
+    /// lost sight of the player, walks to where it saw it last and looks around
+    LostTrack { last_seen: GridPos, turns_left: u32 },
+}
+
+/// How many actions a devil keeps searching once it reached where it lost track of the player
+const SEARCH_TURNS: u32 = 5;
+const DEVIL_VIEW_RANGE: usize = 8;
 
 pub(super) fn plugin(app: &mut App) {
-    app.add_systems(Update, take_turn.run_if(in_state(TurnState::Environment)));
+    app.register_type::<DevilState>().add_systems(
+        Update,
+        (
+            take_turn
+                .run_if(in_state(Pause::Running))
+                .run_if(in_state(TurnState::Environment))
+                .run_if(resource_exists::<TileRegistry>),
+            announce_devils_in_view,
+        ),
+    );
 }
 
-pub fn spawn_devil(commands: &mut Commands, asset_server: &AssetServer, world_pos: Vec3) {
-    let image = asset_server.load("images/devil.png");
-    commands.spawn((
-        Name::new("Devil"),
-        Devil,
-        Transform {
-            translation: world_pos,
-            ..default()
-        },
-        GridMovement {
-            current_pos: GridPos::from_world_pos(world_pos.xy()),
-            target_pos: None,
-        },
-        TurnTaker {
-            actions_per_turn: 1,
-            actions_remaining: 1,
-        },
-        Visibility::Hidden,
-        Sprite {
-            image,
+pub(super) fn presentation_plugin(app: &mut App) {
+    app.add_systems(Update, add_sprite);
+}
+
+pub fn spawn_devil(commands: &mut Commands, world_pos: Vec3) -> Entity {
+    commands
+        .spawn((
+            Name::new("Devil"),
+            Devil,
+            DevilState::default(),
+            Transform {
+                translation: world_pos,
+                ..default()
+            },
+            GridMovement {
+                current_pos: GridPos::from_world_pos(world_pos.xy()),
+                target_pos: None,
+            },
+            TurnTaker::default(),
+            FieldOfView::new(DEVIL_VIEW_RANGE),
+            Health::new(6),
+            Attack(2),
+            Defense(0),
+            Visibility::Hidden,
+            StateScoped(Screen::Gameplay),
+        ))
+        .id()
+}
+
+fn add_sprite(
+    mut commands: Commands,
+    devil_query: Query<Entity, Added<Devil>>,
+    assets: Res<GameAssets>,
+) {
+    for entity in devil_query.iter() {
+        commands.entity(entity).insert(Sprite {
+            image: assets.devil.clone(),
             custom_size: Some(Vec2 {
                 x: TILE_SIZE.x,
                 y: TILE_SIZE.y,
             }),
             ..default()
-        },
-    ));
+        });
+    }
 }
 
 fn take_turn(
-    mut movement_query: Query<(Entity, &mut TurnTaker, &mut GridMovement, Option<&Devil>)>,
-    chunks_query: Query<(
-        &TileStorage,
-        &TilemapSize,
-        &TilemapGridSize,
-        &TilemapType,
-        &Transform,
-    )>,
-    tile_query: Query<&TileTextureIndex>,
+    mut devil_query: Query<
+        (
+            Entity,
+            &mut TurnTaker,
+            &mut GridMovement,
+            &mut DevilState,
+            &FieldOfView,
+        ),
+        With<Devil>,
+    >,
+    player_query: Query<(Entity, &GridMovement), (With<Player>, Without<Devil>, Without<Dead>)>,
+    world_grid: Res<WorldGrid>,
+    tiles: Res<TileRegistry>,
+    mut occupancy: ResMut<Occupancy>,
+    topology: Res<MovementTopology>,
+    budget: Res<PathBudget>,
+    mut path_cache: ResMut<PathCache>,
+    mut turn_queue: ResMut<TurnQueue>,
+    mut game_rng: ResMut<GameRng>,
+    mut attacks: EventWriter<AttackEvent>,
+) {
+    let player = player_query
+        .get_single()
+        .ok()
+        .map(|(player, movement)| (player, movement.current_pos));
+
+    // devils act one after another in the order of the queue, until it is the player's turn
+    while let Some((entity, queued_turn)) = turn_queue.current() {
+        let Ok((entity, mut turn_taker, mut grid_movement, mut state, fov)) =
+            devil_query.get_mut(entity)
+        else {
+            break;
+        };
+        if turn_taker.next_turn != queued_turn {
+            // acted since it was queued, its current entry is further back
+            turn_queue.pop();
+            continue;
+        }
+        if grid_movement.target_pos.is_some() {
+            // wait until it arrived, nobody may skip ahead of it
+            break;
+        }
+        turn_queue.pop();
+
+        let seen_player = player.filter(|(_, pos)| fov.visible_positions.contains(pos));
+        *state = next_state(
+            *state,
+            seen_player.map(|(_, pos)| pos),
+            grid_movement.current_pos,
+        );
+
+        // attack the player instead of moving when it is right next to us
+        if let DevilState::Chasing { .. } = *state
+            && let Some((player, player_pos)) = seen_player
+            && GameGrid::can_step(
+                &grid_movement.current_pos,
+                &player_pos,
+                *topology,
+                &world_grid,
+                &tiles,
+            )
+        {
+            attacks.send(AttackEvent {
+                attacker: entity,
+                target: player,
+            });
+            turn_taker.spend(ACTION_COST);
+            continue;
+        }
+
+        let new_pos = match *state {
+            // stand still for a moment, giving the player a chance to react
+            DevilState::Noticed { .. } => None,
+            DevilState::Chasing { last_seen } => step_towards(
+                entity,
+                &grid_movement,
+                last_seen,
+                &world_grid,
+                &tiles,
+                &occupancy,
+                *topology,
+                &budget,
+                &mut path_cache,
+            ),
+            DevilState::LostTrack {
+                last_seen,
+                turns_left,
+            } if grid_movement.current_pos != last_seen => {
+                let step = step_towards(
+                    entity,
+                    &grid_movement,
+                    last_seen,
+                    &world_grid,
+                    &tiles,
+                    &occupancy,
+                    *topology,
+                    &budget,
+                    &mut path_cache,
+                );
+                if step.is_none() {
+                    // there is no way to get any closer, search right here instead
+                    *state = DevilState::LostTrack {
+                        last_seen: grid_movement.current_pos,
+                        turns_left,
+                    };
+                }
+                step
+            }
+            DevilState::Wandering | DevilState::LostTrack { .. } => random_step(
+                entity,
+                &grid_movement,
+                &mut game_rng,
+                &world_grid,
+                &tiles,
+                &occupancy,
+                *topology,
+            ),
+        };
+
+        if let Some(new_pos) = new_pos
+            && occupancy.reserve(entity, new_pos)
+        {
+            grid_movement.target_pos = Some(new_pos);
+            turn_taker.spend(move_cost(&new_pos, &world_grid, &tiles));
+        } else {
+            // standing still still takes an action
+            turn_taker.spend(ACTION_COST);
+        }
+    }
+}
+
+/// Log every devil that comes into the player's view
+fn announce_devils_in_view(
+    devil_query: Query<(Entity, &GridMovement), With<Devil>>,
+    player_query: Query<&FieldOfView, With<Player>>,
+    mut in_view: Local<HashSet<Entity>>,
+    mut log: EventWriter<LogMessage>,
 ) {
-    let mut occupied_positions: Vec<_> = movement_query
+    let Ok(fov) = player_query.get_single() else {
+        in_view.clear();
+        return;
+    };
+
+    let visible: HashSet<Entity> = devil_query
         .iter()
-        .map(|(entity, _, grid_movement, _)| {
-            (entity, grid_movement.current_pos, grid_movement.target_pos)
-        })
+        .filter(|(_, movement)| fov.visible_positions.contains(&movement.current_pos))
+        .map(|(entity, _)| entity)
         .collect();
 
-    let devils_to_move: Vec<_> = movement_query
-        .iter_mut()
-        .filter(|(_, _, grid_movement, devil)| {
-            devil.is_some() && grid_movement.target_pos.is_none()
-        })
-        .collect();
+    let appeared = visible.difference(&in_view).count();
+    if appeared == 1 {
+        log.send(LogMessage::new(LogKind::Danger, "A devil comes into view."));
+    } else if appeared > 1 {
+        log.send(LogMessage::new(
+            LogKind::Danger,
+            format!("{appeared} devils come into view."),
+        ));
+    }
+
+    *in_view = visible;
+}
+
+/// Advance the state machine by one action, given where the player is if the devil can see it
+/// and where the devil stands
+fn next_state(state: DevilState, seen_player_pos: Option<GridPos>, pos: GridPos) -> DevilState {
+    match (state, seen_player_pos) {
+        (DevilState::Wandering | DevilState::LostTrack { .. }, Some(player_pos)) => {
+            DevilState::Noticed { player_pos }
+        }
+        (DevilState::Noticed { .. } | DevilState::Chasing { .. }, Some(player_pos)) => {
+            DevilState::Chasing {
+                last_seen: player_pos,
+            }
+        }
+        (
+            DevilState::Noticed {
+                player_pos: last_seen,
+            }
+            | DevilState::Chasing { last_seen },
+            None,
+        ) => DevilState::LostTrack {
+            last_seen,
+            turns_left: SEARCH_TURNS,
+        },
+        // the search only starts once the devil got to where it saw the player last
+        (DevilState::LostTrack { last_seen, .. }, None) if pos != last_seen => state,
+        (DevilState::LostTrack { turns_left: 0, .. }, None) => DevilState::Wandering,
+        (
+            DevilState::LostTrack {
+                last_seen,
+                turns_left,
+            },
+            None,
+        ) => DevilState::LostTrack {
+            last_seen,
+            turns_left: turns_left - 1,
+        },
+        (DevilState::Wandering, None) => DevilState::Wandering,
+    }
+}
+
+/// Next position on the path to `goal`, if the devil can step there
+fn step_towards(
+    entity: Entity,
+    grid_movement: &GridMovement,
+    goal: GridPos,
+    world_grid: &WorldGrid,
+    tiles: &TileRegistry,
+    occupancy: &Occupancy,
+    topology: MovementTopology,
+    budget: &PathBudget,
+    path_cache: &mut PathCache,
+) -> Option<GridPos> {
+    let result = path_cache.find_path(
+        world_grid,
+        tiles,
+        occupancy,
+        topology,
+        budget,
+        grid_movement.current_pos,
+        goal,
+    )?;
+    let next_pos = *result.path.get(1)?;
 
-    devils_to_move
+    // the goal may be occupied, e.g. by the player we are chasing
+    (!occupancy.is_occupied_by_other(&next_pos, entity)).then_some(next_pos)
+}
+
+/// A random neighbouring position the devil can step to
+fn random_step(
+    entity: Entity,
+    grid_movement: &GridMovement,
+    game_rng: &mut GameRng,
+    world_grid: &WorldGrid,
+    tiles: &TileRegistry,
+    occupancy: &Occupancy,
+    topology: MovementTopology,
+) -> Option<GridPos> {
+    let mut shuffled_directions = topology.directions().to_vec();
+    shuffled_directions.shuffle(&mut game_rng.next_rng());
+
+    shuffled_directions
         .into_iter()
-        .for_each(|(entity, mut turn_taker, mut grid_movement, _)| {
-            let directions = [(1, 0), (-1, 0), (0, 1), (0, -1)];
-            let mut rng = rand::rng();
-            let mut shuffled_directions = directions.to_vec();
-            shuffled_directions.shuffle(&mut rng);
-
-            for (dx, dy) in shuffled_directions {
-                let new_pos = GridPos {
-                    x: grid_movement.current_pos.x + dx,
-                    y: grid_movement.current_pos.y + dy,
-                };
-
-                if !GameGrid::is_walkable(&new_pos, &chunks_query, &tile_query) {
-                    continue;
+        .map(|direction| grid_movement.current_pos.offset(direction))
+        .find(|new_pos| {
+            GameGrid::can_step(
+                &grid_movement.current_pos,
+                new_pos,
+                topology,
+                world_grid,
+                tiles,
+            ) && !occupancy.is_occupied_by_other(new_pos, entity)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_starts_once_last_seen_is_reached() {
+        let last_seen = GridPos { x: 10, y: 0 };
+        let mut state = next_state(
+            DevilState::Chasing { last_seen },
+            None,
+            GridPos { x: 0, y: 0 },
+        );
+
+        // walking there takes longer than the search, but doesn't count towards it
+        for x in 0..SEARCH_TURNS * 2 {
+            state = next_state(
+                state,
+                None,
+                GridPos {
+                    x: x as i32 % 10,
+                    y: 0,
+                },
+            );
+            assert_eq!(
+                state,
+                DevilState::LostTrack {
+                    last_seen,
+                    turns_left: SEARCH_TURNS
                 }
+            );
+        }
 
-                let is_occupied =
-                    occupied_positions
-                        .iter()
-                        .any(|(other_entity, other_current, other_target)| {
-                            if *other_entity == entity {
-                                return false;
-                            }
-                            *other_current == new_pos
-                                || other_target.is_some_and(|pos| pos == new_pos)
-                        });
-
-                if !is_occupied {
-                    // update our entities old occupied_position so other entities can't move to
-                    // its new position
-                    if let Some((_, _, target_pos)) = occupied_positions
-                        .iter_mut()
-                        .find(|(other_entity, _, _)| *other_entity == entity)
-                    {
-                        *target_pos = Some(new_pos);
-                    }
-
-                    grid_movement.target_pos = Some(new_pos);
-                    turn_taker.actions_remaining -= 1;
-                    break;
+        for turns_left in (0..SEARCH_TURNS).rev() {
+            state = next_state(state, None, last_seen);
+            assert_eq!(
+                state,
+                DevilState::LostTrack {
+                    last_seen,
+                    turns_left
                 }
-            }
+            );
+        }
+        assert_eq!(next_state(state, None, last_seen), DevilState::Wandering);
+    }
 
-            // we cant move in any direction, but still take an action
-            if grid_movement.target_pos.is_none() {
-                turn_taker.actions_remaining -= 1;
-            }
-        });
+    #[test]
+    fn seeing_the_player_again_interrupts_the_search() {
+        let last_seen = GridPos { x: 3, y: 3 };
+        let player_pos = GridPos { x: 5, y: 3 };
+        let state = DevilState::LostTrack {
+            last_seen,
+            turns_left: 2,
+        };
+
+        assert_eq!(
+            next_state(state, Some(player_pos), last_seen),
+            DevilState::Noticed { player_pos }
+        );
+    }
 }
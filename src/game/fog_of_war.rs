@@ -3,7 +3,11 @@ use bevy_ecs_tilemap::prelude::*;
 
 use crate::components::{FieldOfView, Player};
 
-use super::{map::GridPos, map::TILE_SIZE};
+use super::{map::GridPos, world_grid::WorldGrid};
+
+/// Sent whenever the player sees positions it never saw before
+#[derive(Event, Debug, Clone)]
+pub struct FogRevealed(pub Vec<GridPos>);
 
 #[derive(Resource, Default)]
 pub struct FogOfWar {
@@ -11,34 +15,62 @@ pub struct FogOfWar {
     viewed_positions: HashSet<GridPos>,
 }
 
+impl FogOfWar {
+    pub fn viewed_positions(&self) -> &HashSet<GridPos> {
+        &self.viewed_positions
+    }
+
+    /// Replace the remembered positions, e.g. when loading a save game
+    pub fn set_viewed_positions(&mut self, positions: impl IntoIterator<Item = GridPos>) {
+        self.viewed_positions = positions.into_iter().collect();
+    }
+}
+
 pub fn plugin(app: &mut App) {
     app.insert_resource(FogOfWar::default())
-        .add_systems(Update, (update_viewed_positions, render_viewed_positions));
+        .add_event::<FogRevealed>()
+        .add_systems(Update, update_viewed_positions);
+}
+
+pub(super) fn presentation_plugin(app: &mut App) {
+    app.add_systems(Update, render_viewed_positions);
 }
 
 /// store all player fov position in fog_of_war.viewed_positions
 /// TODO: PERF: only call this when the player fov was recalculated...
 fn update_viewed_positions(
     mut fog_of_war: ResMut<FogOfWar>,
-    player_query: Query<(&Transform, &FieldOfView)>,
+    player_query: Query<&FieldOfView, With<Player>>,
+    mut revealed: EventWriter<FogRevealed>,
 ) {
-    for (_, fov) in player_query.iter() {
-        for pos in &fov.visible_positions {
-            fog_of_war.viewed_positions.insert(*pos);
+    for fov in player_query.iter() {
+        let new_positions: Vec<GridPos> = fov
+            .visible_positions
+            .iter()
+            .filter(|pos| !fog_of_war.viewed_positions.contains(*pos))
+            .copied()
+            .collect();
+        if new_positions.is_empty() {
+            continue;
         }
+
+        fog_of_war
+            .viewed_positions
+            .extend(new_positions.iter().copied());
+        revealed.send(FogRevealed(new_positions));
     }
 }
 
 fn render_viewed_positions(
     fog_of_war: Res<FogOfWar>,
     player_query: Query<&FieldOfView, With<Player>>,
-    mut tile_query: Query<(&TilePos, &mut TileVisible, &mut TileColor, &Parent)>,
-    tilemap_query: Query<&Transform>,
+    mut tile_query: Query<(&mut TileVisible, &mut TileColor)>,
+    world_grid: Res<WorldGrid>,
     debug_options: Res<UiDebugOptions>,
 ) {
     if debug_options.enabled {
         // make all tiles visible
-        for (_, mut tile_visible, mut tile_color, _) in tile_query.iter_mut() {
+        for (mut tile_visible, mut tile_color) in tile_query.iter_mut() {
             tile_visible.0 = true;
             tile_color.0 = Color::WHITE;
         }
@@ -50,21 +82,19 @@ fn render_viewed_positions(
         return;
     };
 
-    for (tile_pos, mut tile_visible, mut tile_color, parent) in tile_query.iter_mut() {
-        let Ok(chunk_transform) = tilemap_query.get(parent.get()) else {
+    let visible_positions: HashSet<GridPos> =
+        player_fov.visible_positions.iter().copied().collect();
+
+    for (grid_pos, tile) in world_grid.iter() {
+        let Ok((mut tile_visible, mut tile_color)) = tile_query.get_mut(tile.entity) else {
             continue;
         };
 
-        let world_pos =
-            tile_pos.center_in_world(&TilemapGridSize::from(TILE_SIZE), &TilemapType::Square);
-        let world_pos = (chunk_transform.compute_matrix() * world_pos.extend(0.0).extend(1.0)).xy();
-        let grid_pos = GridPos::from_world_pos(world_pos);
-
-        if player_fov.visible_positions.contains(&grid_pos) {
+        if visible_positions.contains(grid_pos) {
             // Fully visible in fov
             tile_visible.0 = true;
             tile_color.0 = Color::WHITE;
-        } else if fog_of_war.viewed_positions.contains(&grid_pos) {
+        } else if fog_of_war.viewed_positions.contains(grid_pos) {
             // Partially visible (visited before) - we darken the tile
             tile_visible.0 = true;
             tile_color.0 = Color::srgba(1.0, 1.0, 1.0, 0.25); // render with less opacity!
@@ -1,11 +1,12 @@
 use bevy::{dev_tools::ui_debug_overlay::UiDebugOptions, prelude::*};
-use bevy_ecs_tilemap::prelude::*;
 
 use crate::components::TurnTaker;
 
 use super::{
     map::{GameGrid, GridPos, TILE_SIZE},
     player::Player,
+    tiles::TileRegistry,
+    world_grid::WorldGrid,
 };
 use doryen_fov::{FovAlgorithm, FovRestrictive, MapData};
 
@@ -46,26 +47,14 @@ impl FieldOfView {
     }
 
     /// Update field of view from current position
-    pub fn update(
-        &mut self,
-        position: Vec2,
-        chunks_query: &Query<(
-            &TileStorage,
-            &TilemapSize,
-            &TilemapGridSize,
-            &TilemapType,
-            &Transform,
-        )>,
-        tile_query: &Query<&TileTextureIndex>,
-    ) {
+    pub fn update(&mut self, position: Vec2, world_grid: &WorldGrid, tiles: &TileRegistry) {
         self.fov_map.clear_fov();
         let center_pos = GridPos::from_world_pos(position);
         let positions_in_view_range = self.get_positions_in_view_range(&center_pos);
 
         // Set all positions that are in the FOV
         for pos in &positions_in_view_range {
-            // all walkable positions are transparent
-            let is_transparent = GameGrid::is_walkable(pos, chunks_query, tile_query);
+            let is_transparent = GameGrid::is_transparent(pos, world_grid, tiles);
 
             // Convert to FOV grid coordinates
             let fov_x = ((pos.x - center_pos.x) + self.view_range as i32) as usize;
@@ -96,29 +85,32 @@ impl FieldOfView {
 pub fn plugin(app: &mut App) {
     app.add_systems(
         PostUpdate,
-        (
-            update_fov,
-            visibility_of_entities_in_player_fov,
-            show_debug_grid,
-        ),
+        update_fov.run_if(resource_exists::<TileRegistry>),
     );
 }
 
-// System to update FOV for all entities that have one
-// PERF: only need to call this if the entity has moved
+pub(super) fn presentation_plugin(app: &mut App) {
+    app.add_systems(
+        PostUpdate,
+        (visibility_of_entities_in_player_fov, show_debug_grid),
+    );
+}
+
+// System to update FOV for all entities that have one.
+// Only entities that moved are updated, unless the world grid changed
 fn update_fov(
-    mut query: Query<(&Transform, &mut FieldOfView)>,
-    chunks_query: Query<(
-        &TileStorage,
-        &TilemapSize,
-        &TilemapGridSize,
-        &TilemapType,
-        &Transform,
-    )>,
-    tile_query: Query<&TileTextureIndex>,
+    mut query: Query<(Ref<Transform>, &mut FieldOfView)>,
+    world_grid: Res<WorldGrid>,
+    tiles: Res<TileRegistry>,
+    mut last_revision: Local<Option<u64>>,
 ) {
+    let grid_changed = *last_revision != Some(world_grid.revision()) || tiles.is_changed();
+    *last_revision = Some(world_grid.revision());
+
     for (transform, mut fov) in query.iter_mut() {
-        fov.update(transform.translation.xy(), &chunks_query, &tile_query);
+        if grid_changed || transform.is_changed() {
+            fov.update(transform.translation.xy(), &world_grid, &tiles);
+        }
     }
 }
 
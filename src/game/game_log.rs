@@ -0,0 +1,324 @@
+use std::{collections::VecDeque, fmt::Write as _, fs, io, path::Path};
+
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+
+use crate::states::Screen;
+
+use super::{
+    input::{Action, ActionState},
+    turns::TurnQueue,
+};
+
+/// Where the log is written to for bug reports
+const LOG_DUMP_PATH: &str = "logs/game_log.txt";
+/// max amount of entries kept, older ones are dropped
+const LOG_CAPACITY: usize = 500;
+/// max amount of entries shown in the log panel
+const PANEL_ENTRIES: usize = 100;
+/// pixels scrolled per line of a mouse wheel
+const SCROLL_LINE_HEIGHT: f32 = 20.0;
+
+/// What a message is about, decides its color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LogKind {
+    /// anything not worth highlighting, e.g. moving around
+    Info,
+    /// something the player tried didn't work
+    Blocked,
+    /// an enemy showed up or the player is in trouble
+    Danger,
+    Combat,
+    /// saving, loading and other things outside the game world
+    System,
+}
+
+impl LogKind {
+    pub fn color(&self) -> Color {
+        match self {
+            LogKind::Info => Color::srgb(0.8, 0.8, 0.8),
+            LogKind::Blocked => Color::srgb(0.6, 0.6, 0.6),
+            LogKind::Danger => Color::srgb(1.0, 0.35, 0.2),
+            LogKind::Combat => Color::srgb(1.0, 0.8, 0.3),
+            LogKind::System => Color::srgb(0.4, 0.7, 1.0),
+        }
+    }
+}
+
+/// Send this to add a message to the GameLog, it is stamped with the current turn
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct LogMessage {
+    pub kind: LogKind,
+    pub text: String,
+}
+
+impl LogMessage {
+    pub fn new(kind: LogKind, text: impl Into<String>) -> Self {
+        Self {
+            kind,
+            text: text.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    /// turn of the last time the message was logged
+    pub turn: u64,
+    pub kind: LogKind,
+    pub text: String,
+    /// how often the message was logged in a row
+    pub count: u32,
+}
+
+impl std::fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.turn, self.text)?;
+        if self.count > 1 {
+            write!(f, " (x{})", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Everything that happened in the current run, oldest entry first.
+/// Repeating the last message increases its count instead of adding another entry.
+#[derive(Resource, Debug, Default)]
+pub struct GameLog {
+    entries: VecDeque<LogEntry>,
+    /// entries added since the log was cleared, including the dropped ones
+    added: u64,
+}
+
+impl GameLog {
+    pub fn push(&mut self, turn: u64, message: LogMessage) {
+        if let Some(last) = self.entries.back_mut()
+            && last.kind == message.kind
+            && last.text == message.text
+        {
+            last.turn = turn;
+            last.count += 1;
+            return;
+        }
+
+        self.entries.push_back(LogEntry {
+            turn,
+            kind: message.kind,
+            text: message.text,
+            count: 1,
+        });
+        self.added += 1;
+        if self.entries.len() > LOG_CAPACITY {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn entries(&self) -> impl ExactSizeIterator<Item = &LogEntry> {
+        self.entries.iter()
+    }
+
+    /// How many entries were added since the log was cleared.
+    /// Repeating the last message doesn't add an entry.
+    pub fn added(&self) -> u64 {
+        self.added
+    }
+
+    /// The entry that was added as the `index`th one, if it wasn't dropped yet
+    pub fn entry(&self, index: u64) -> Option<&LogEntry> {
+        let first = self.added - self.entries.len() as u64;
+        self.entries.get(index.checked_sub(first)? as usize)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.added = 0;
+    }
+
+    /// Write every entry to a text file, one per line
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut content = String::new();
+        for entry in &self.entries {
+            let _ = writeln!(content, "{entry}");
+        }
+        fs::write(path, content)
+    }
+}
+
+#[derive(Component, Default)]
+struct LogPanel {
+    /// GameLog::added when the panel was last updated
+    shown: u64,
+}
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<GameLog>()
+        .add_event::<LogMessage>()
+        .add_systems(Last, collect_log_messages);
+}
+
+pub(super) fn presentation_plugin(app: &mut App) {
+    app.add_systems(OnEnter(Screen::Gameplay), spawn_log_panel)
+        .add_systems(
+            Update,
+            (
+                dump_game_log,
+                update_log_panel.run_if(resource_changed::<GameLog>),
+                scroll_log_panel,
+            ),
+        );
+}
+
+fn collect_log_messages(
+    mut messages: EventReader<LogMessage>,
+    mut game_log: ResMut<GameLog>,
+    turn_queue: Res<TurnQueue>,
+) {
+    for message in messages.read() {
+        debug!("{}", message.text);
+        game_log.push(turn_queue.turn(), message.clone());
+    }
+}
+
+fn dump_game_log(action_state: Res<ActionState>, game_log: Res<GameLog>) {
+    if !action_state.just_pressed(Action::DumpLog) {
+        return;
+    }
+
+    match game_log.write(Path::new(LOG_DUMP_PATH)) {
+        Ok(()) => info!("wrote game log to {LOG_DUMP_PATH}"),
+        Err(err) => error!("could not write game log to {LOG_DUMP_PATH}: {err}"),
+    }
+}
+
+fn spawn_log_panel(mut commands: Commands) {
+    commands.spawn((
+        Name::new("Log Panel"),
+        LogPanel::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.0),
+            left: Val::Px(12.0),
+            width: Val::Px(420.0),
+            height: Val::Px(160.0),
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Px(6.0)),
+            overflow: Overflow::scroll_y(),
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.5)),
+        Interaction::default(),
+        ScrollPosition::default(),
+        StateScoped(Screen::Gameplay),
+    ));
+}
+
+/// Show the latest entries, newest at the bottom, and scroll down to them.
+/// Only entries added since the last update are spawned, the newest shown entry is updated in
+/// case it was repeated since.
+fn update_log_panel(
+    mut commands: Commands,
+    game_log: Res<GameLog>,
+    mut panel_query: Query<(
+        Entity,
+        &mut LogPanel,
+        &mut ScrollPosition,
+        Option<&Children>,
+    )>,
+    mut text_query: Query<&mut Text>,
+) {
+    for (panel, mut log_panel, mut scroll_position, children) in panel_query.iter_mut() {
+        let mut lines: Vec<Entity> = children
+            .map(|children| children.to_vec())
+            .unwrap_or_default();
+
+        if game_log.added() < log_panel.shown {
+            // the log was cleared
+            commands.entity(panel).despawn_descendants();
+            lines.clear();
+            log_panel.shown = 0;
+        }
+
+        if let Some(last_line) = lines.last()
+            && let Some(entry) = game_log.entry(log_panel.shown.saturating_sub(1))
+            && let Ok(mut text) = text_query.get_mut(*last_line)
+        {
+            text.0 = entry.to_string();
+        }
+
+        let new_entries = (game_log.added() - log_panel.shown).min(PANEL_ENTRIES as u64) as usize;
+        let skipped = game_log.entries().len().saturating_sub(new_entries);
+        commands.entity(panel).with_children(|children| {
+            for entry in game_log.entries().skip(skipped) {
+                children.spawn((
+                    Text::new(entry.to_string()),
+                    TextFont {
+                        font_size: 14.0,
+                        ..default()
+                    },
+                    TextColor(entry.kind.color()),
+                ));
+            }
+        });
+
+        let shown_lines = lines.len() + game_log.entries().len() - skipped;
+        let dropped = shown_lines.saturating_sub(PANEL_ENTRIES);
+        for line in lines.into_iter().take(dropped) {
+            commands.entity(line).despawn_recursive();
+        }
+
+        if new_entries > 0 {
+            // the layout clamps this to the bottom of the panel
+            scroll_position.offset_y = f32::MAX;
+        }
+        log_panel.shown = game_log.added();
+    }
+}
+
+fn scroll_log_panel(
+    mut wheel_events: EventReader<MouseWheel>,
+    mut panel_query: Query<(&Interaction, &mut ScrollPosition), With<LogPanel>>,
+) {
+    for event in wheel_events.read() {
+        let delta = match event.unit {
+            MouseScrollUnit::Line => event.y * SCROLL_LINE_HEIGHT,
+            MouseScrollUnit::Pixel => event.y,
+        };
+
+        for (interaction, mut scroll_position) in panel_query.iter_mut() {
+            if *interaction == Interaction::None {
+                continue;
+            }
+            scroll_position.offset_y -= delta;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_keep_their_index_when_older_ones_are_dropped() {
+        let mut game_log = GameLog::default();
+        for index in 0..LOG_CAPACITY + 10 {
+            game_log.push(0, LogMessage::new(LogKind::Info, index.to_string()));
+        }
+        // repeating the last message doesn't add an entry
+        game_log.push(
+            1,
+            LogMessage::new(LogKind::Info, (LOG_CAPACITY + 9).to_string()),
+        );
+
+        assert_eq!(game_log.added(), LOG_CAPACITY as u64 + 10);
+        assert_eq!(game_log.entry(9), None);
+        assert_eq!(game_log.entry(10).unwrap().text, "10");
+        assert_eq!(game_log.entry(LOG_CAPACITY as u64 + 9).unwrap().count, 2);
+        assert_eq!(game_log.entry(LOG_CAPACITY as u64 + 10), None);
+    }
+}
@@ -0,0 +1,308 @@
+use std::time::Duration;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::map::MovementTopology;
+
+pub const INPUT_BINDINGS_PATH: &str = "data/input.bindings.ron";
+
+/// Everything the player can ask the game to do.
+/// Systems react to actions instead of keys, which keys trigger them is up to the InputBindings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveNorth,
+    MoveSouth,
+    MoveEast,
+    MoveWest,
+    MoveNorthEast,
+    MoveNorthWest,
+    MoveSouthEast,
+    MoveSouthWest,
+    Wait,
+    Rest,
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    CenterCamera,
+    ZoomIn,
+    ZoomOut,
+    /// select a tile with a cursor instead of moving the player
+    ToggleCursor,
+    /// move a cursor around to examine tiles, without walking there
+    Look,
+    /// move the focus to the previous button of a menu
+    MenuUp,
+    /// move the focus to the next button of a menu
+    MenuDown,
+    Confirm,
+    Cancel,
+    QuickSave,
+    QuickLoad,
+    /// pause or continue the game
+    Pause,
+    /// write the message log to a file, e.g. for bug reports
+    DumpLog,
+    /// switch between the minimap and the full screen map
+    ToggleMap,
+    /// show the outlines of UI nodes, only in dev builds
+    ToggleDebug,
+}
+
+impl Action {
+    pub const MOVEMENT: [Action; 8] = [
+        Action::MoveNorth,
+        Action::MoveSouth,
+        Action::MoveEast,
+        Action::MoveWest,
+        Action::MoveNorthEast,
+        Action::MoveNorthWest,
+        Action::MoveSouthEast,
+        Action::MoveSouthWest,
+    ];
+
+    /// Grid direction of movement actions
+    pub fn direction(&self) -> Option<IVec2> {
+        match self {
+            Action::MoveNorth => Some(IVec2::new(0, 1)),
+            Action::MoveSouth => Some(IVec2::new(0, -1)),
+            Action::MoveEast => Some(IVec2::new(1, 0)),
+            Action::MoveWest => Some(IVec2::new(-1, 0)),
+            Action::MoveNorthEast => Some(IVec2::new(1, 1)),
+            Action::MoveNorthWest => Some(IVec2::new(-1, 1)),
+            Action::MoveSouthEast => Some(IVec2::new(1, -1)),
+            Action::MoveSouthWest => Some(IVec2::new(-1, -1)),
+            _ => None,
+        }
+    }
+
+    /// Whether holding the action down triggers it again and again
+    pub fn repeats(&self) -> bool {
+        self.direction().is_some() || matches!(self, Action::MenuUp | Action::MenuDown)
+    }
+}
+
+/// Which keys and gamepad buttons trigger which action, as loaded from a `.bindings.ron` asset.
+/// Only inserted as a resource once the asset finished loading.
+#[derive(Asset, Resource, TypePath, Debug, Clone, Deserialize)]
+pub struct InputBindings {
+    pub keys: HashMap<Action, Vec<KeyCode>>,
+    #[serde(default)]
+    pub buttons: HashMap<Action, Vec<GamepadButton>>,
+    /// how far the left stick has to be pushed to move, between 0 and 1
+    #[serde(default = "default_stick_deadzone")]
+    pub stick_deadzone: f32,
+    /// seconds a repeating action has to be held before it repeats
+    pub repeat_delay: f32,
+    /// seconds between repeats while it is held
+    pub repeat_interval: f32,
+}
+
+fn default_stick_deadzone() -> f32 {
+    0.5
+}
+
+/// State of every action this frame, gathered from all bound inputs
+#[derive(Resource, Debug, Default)]
+pub struct ActionState {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+    triggered: HashSet<Action>,
+    /// how long repeating actions have been held, and when they repeat next
+    held: HashMap<Action, (Duration, Duration)>,
+    camera_pan: Vec2,
+    camera_zoom: f32,
+}
+
+impl ActionState {
+    /// Analog camera movement from the right stick, each axis between -1 and 1
+    pub fn camera_pan(&self) -> Vec2 {
+        self.camera_pan
+    }
+
+    /// Analog zoom from the triggers, positive zooms in
+    pub fn camera_zoom(&self) -> f32 {
+        self.camera_zoom
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    /// Just pressed, or held long enough for a repeating action to repeat
+    pub fn triggered(&self, action: Action) -> bool {
+        self.triggered.contains(&action)
+    }
+
+    /// Replace the state with the actions pressed this frame
+    pub fn update(&mut self, pressed: HashSet<Action>, delta: Duration, bindings: &InputBindings) {
+        let repeat_delay = Duration::from_secs_f32(bindings.repeat_delay);
+        let repeat_interval = Duration::from_secs_f32(bindings.repeat_interval);
+
+        self.just_pressed = pressed.difference(&self.pressed).copied().collect();
+        self.triggered = self.just_pressed.clone();
+        self.held.retain(|action, _| pressed.contains(action));
+
+        for action in pressed.iter().filter(|action| action.repeats()) {
+            let Some((held, next_repeat)) = self.held.get_mut(action) else {
+                self.held.insert(*action, (Duration::ZERO, repeat_delay));
+                continue;
+            };
+
+            *held += delta;
+            if *held >= *next_repeat {
+                *next_repeat += repeat_interval;
+                self.triggered.insert(*action);
+            }
+        }
+
+        self.pressed = pressed;
+    }
+}
+
+#[derive(Resource)]
+struct InputBindingsHandle(Handle<InputBindings>);
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_asset::<InputBindings>()
+        .register_asset_loader(InputBindingsLoader)
+        .init_resource::<ActionState>()
+        .add_systems(Startup, load_input_bindings)
+        .add_systems(
+            PreUpdate,
+            (
+                update_input_bindings,
+                update_action_state.run_if(resource_exists::<InputBindings>),
+            )
+                .chain()
+                .after(bevy::input::InputSystem),
+        );
+}
+
+fn load_input_bindings(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(InputBindingsHandle(asset_server.load(INPUT_BINDINGS_PATH)));
+}
+
+/// (Re)insert the bindings whenever they finished loading or were hot reloaded
+fn update_input_bindings(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<InputBindings>>,
+    handle: Res<InputBindingsHandle>,
+    bindings: Res<Assets<InputBindings>>,
+) {
+    for event in events.read() {
+        if !event.is_loaded_with_dependencies(&handle.0) && !event.is_modified(&handle.0) {
+            continue;
+        }
+
+        let Some(bindings) = bindings.get(&handle.0) else {
+            continue;
+        };
+
+        commands.insert_resource(bindings.clone());
+    }
+}
+
+fn update_action_state(
+    mut action_state: ResMut<ActionState>,
+    bindings: Res<InputBindings>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    topology: Res<MovementTopology>,
+    time: Res<Time>,
+) {
+    let mut pressed: HashSet<Action> = bindings
+        .keys
+        .iter()
+        .filter(|(_, keys)| keyboard.any_pressed(keys.iter().copied()))
+        .map(|(action, _)| *action)
+        .collect();
+
+    let mut camera_pan = Vec2::ZERO;
+    let mut camera_zoom = 0.0;
+    for gamepad in gamepads.iter() {
+        pressed.extend(
+            bindings
+                .buttons
+                .iter()
+                .filter(|(_, buttons)| gamepad.any_pressed(buttons.iter().copied()))
+                .map(|(action, _)| *action),
+        );
+
+        if let Some(action) = stick_movement(gamepad.left_stick(), &bindings, *topology) {
+            pressed.insert(action);
+        }
+
+        camera_pan += gamepad.right_stick();
+        camera_zoom += gamepad.get(GamepadButton::RightTrigger2).unwrap_or(0.0)
+            - gamepad.get(GamepadButton::LeftTrigger2).unwrap_or(0.0);
+    }
+
+    action_state.update(pressed, time.delta(), &bindings);
+    action_state.camera_pan = camera_pan.clamp_length_max(1.0);
+    action_state.camera_zoom = camera_zoom.clamp(-1.0, 1.0);
+}
+
+/// The movement action the stick points to most, limited to directions the topology allows
+fn stick_movement(
+    stick: Vec2,
+    bindings: &InputBindings,
+    topology: MovementTopology,
+) -> Option<Action> {
+    if stick.length() < bindings.stick_deadzone {
+        return None;
+    }
+
+    Action::MOVEMENT
+        .into_iter()
+        .filter_map(|action| Some((action, action.direction()?)))
+        .filter(|(_, direction)| topology.directions().contains(direction))
+        .max_by(|(_, a), (_, b)| {
+            let a = stick.dot(a.as_vec2().normalize());
+            let b = stick.dot(b.as_vec2().normalize());
+            a.total_cmp(&b)
+        })
+        .map(|(action, _)| action)
+}
+
+#[derive(Default)]
+struct InputBindingsLoader;
+
+#[derive(Debug, Error)]
+enum InputBindingsLoaderError {
+    #[error("could not read input bindings: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse input bindings: {0}")]
+    Ron(#[from] ron::de::SpannedError),
+}
+
+impl AssetLoader for InputBindingsLoader {
+    type Asset = InputBindings;
+    type Settings = ();
+    type Error = InputBindingsLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["bindings.ron"]
+    }
+}
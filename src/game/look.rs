@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+
+use crate::{
+    components::{FieldOfView, Player},
+    states::Screen,
+};
+
+use super::{
+    combat::{Attack, Defense, Health},
+    devil::DevilState,
+    fog_of_war::FogOfWar,
+    map::{GridPos, HoveredTilePos},
+    occupancy::Occupancy,
+    world_grid::WorldGrid,
+};
+
+/// distance between the hovered tile and its tooltip, in logical pixels
+const TOOLTIP_OFFSET: Vec2 = Vec2::new(16.0, -8.0);
+
+/// Describes the hovered tile, as far as the player knows about it
+#[derive(Component)]
+struct Tooltip;
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(OnEnter(Screen::Gameplay), spawn_tooltip)
+        .add_systems(Update, update_tooltip.run_if(in_state(Screen::Gameplay)));
+}
+
+fn spawn_tooltip(mut commands: Commands) {
+    commands.spawn((
+        Name::new("Tooltip"),
+        Tooltip,
+        Node {
+            position_type: PositionType::Absolute,
+            padding: UiRect::all(Val::Px(6.0)),
+            ..default()
+        },
+        Text::default(),
+        TextFont {
+            font_size: 14.0,
+            ..default()
+        },
+        BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.8)),
+        Visibility::Hidden,
+        StateScoped(Screen::Gameplay),
+    ));
+}
+
+fn update_tooltip(
+    hovered_tile_pos: Res<HoveredTilePos>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<IsDefaultUiCamera>>,
+    player_query: Query<(Entity, &FieldOfView), With<Player>>,
+    actor_query: Query<(
+        &Name,
+        Option<&Health>,
+        Option<&Attack>,
+        Option<&Defense>,
+        Option<&DevilState>,
+    )>,
+    name_query: Query<&Name>,
+    world_grid: Res<WorldGrid>,
+    fog_of_war: Res<FogOfWar>,
+    occupancy: Res<Occupancy>,
+    mut tooltip_query: Query<(&mut Node, &mut Text, &mut Visibility), With<Tooltip>>,
+) {
+    let Ok((mut node, mut text, mut visibility)) = tooltip_query.get_single_mut() else {
+        return;
+    };
+
+    let hovered = hovered_tile_pos.0.zip(camera_query.get_single().ok());
+    let (Some((world_pos, (camera, camera_transform))), Ok((player, fov))) =
+        (hovered, player_query.get_single())
+    else {
+        visibility.set_if_neq(Visibility::Hidden);
+        return;
+    };
+    let Ok(viewport_pos) = camera.world_to_viewport(camera_transform, world_pos.extend(0.0)) else {
+        visibility.set_if_neq(Visibility::Hidden);
+        return;
+    };
+
+    let pos = GridPos::from_world_pos(world_pos);
+    let is_visible = fov.visible_positions.contains(&pos);
+    let is_remembered = fog_of_war.viewed_positions().contains(&pos);
+
+    let mut lines = Vec::new();
+    if !is_visible && !is_remembered {
+        // the fog hides everything about tiles the player never saw
+        lines.push("Unexplored".to_string());
+    } else {
+        let tile_name = world_grid
+            .get(&pos)
+            .and_then(|tile| name_query.get(tile.entity).ok())
+            .map_or("Unknown", Name::as_str);
+        let seen = if is_visible { "in view" } else { "remembered" };
+        lines.push(format!("{tile_name} ({seen})"));
+    }
+
+    // actors are only known while they can be seen
+    if is_visible
+        && let Some(actor) = occupancy.occupant(&pos)
+        && let Ok((name, health, attack, defense, devil_state)) = actor_query.get(actor)
+    {
+        if actor == player {
+            lines.push(format!("{name} (you)"));
+        } else {
+            lines.push(name.to_string());
+        }
+        if let Some(health) = health {
+            lines.push(format!("HP {}/{}", health.current, health.max));
+        }
+        if let (Some(attack), Some(defense)) = (attack, defense) {
+            lines.push(format!("Attack {} Defense {}", attack.0, defense.0));
+        }
+        if let Some(devil_state) = devil_state {
+            lines.push(describe_devil_state(devil_state).to_string());
+        }
+    }
+
+    let content = lines.join("\n");
+    if text.0 != content {
+        text.0 = content;
+    }
+    let tooltip_pos = viewport_pos + TOOLTIP_OFFSET;
+    if node.left != Val::Px(tooltip_pos.x) || node.top != Val::Px(tooltip_pos.y) {
+        node.left = Val::Px(tooltip_pos.x);
+        node.top = Val::Px(tooltip_pos.y);
+    }
+    visibility.set_if_neq(Visibility::Inherited);
+}
+
+fn describe_devil_state(state: &DevilState) -> &'static str {
+    match state {
+        DevilState::Wandering => "Wandering around",
+        DevilState::Noticed { .. } => "Noticed you",
+        DevilState::Chasing { .. } => "Chasing you",
+        DevilState::LostTrack { .. } => "Looking for you",
+    }
+}
@@ -1,10 +1,23 @@
-use crate::components::Player;
-use crate::game::devil::spawn_devil;
-use bevy::{prelude::*, utils::HashSet};
+use crate::components::{Player, TurnTaker};
+use crate::game::assets::GameAssets;
+use crate::game::chunk_store::{ActorKind, ChunkManager, StoredActor, StoredChunk};
+use crate::game::combat::Health;
+use crate::game::devil::{Devil, spawn_devil};
+use crate::game::map_generator::{ChunkGates, MapGeneratorKind, SelectedGenerator};
+use crate::game::occupancy::Occupancy;
+use crate::game::path_cache::PathCache;
+use crate::game::tiles::{TileKind, TileRegistry};
+use crate::game::world_grid::{WorldGrid, WorldTile};
+use crate::states::Screen;
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
 use bevy_ecs_tilemap::prelude::*;
 use bresenham::Bresenham;
-use pathfinding::prelude::astar;
-use rand::prelude::*;
+use rand::{prelude::*, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Reverse, collections::BinaryHeap};
 
 #[derive(Component)]
 pub struct GridMovement {
@@ -18,13 +31,240 @@ pub struct GridMovement {
 #[derive(Resource)]
 pub struct HoveredTilePos(pub Option<Vec2>);
 
-#[derive(Default, Debug, Resource)]
-pub struct ChunkManager {
-    pub spawned_chunks: HashSet<IVec2>,
+/// Seed of the current run.
+/// Every chunk is generated purely from this seed and its chunk position, so a chunk that is
+/// despawned and spawned again comes back with the same layout.
+/// Set the `M_ROUGE_SEED` environment variable to replay a specific world.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WorldSeed(pub u64);
+
+impl Default for WorldSeed {
+    fn default() -> Self {
+        let seed = std::env::var("M_ROUGE_SEED")
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or_else(rand::random);
+        Self(seed)
+    }
+}
+
+impl WorldSeed {
+    /// A rng that always yields the same sequence for the same seed and chunk position
+    pub fn chunk_rng(&self, chunk_pos: IVec2) -> StdRng {
+        let chunk_key = ((chunk_pos.x as u32 as u64) << 32) | chunk_pos.y as u32 as u64;
+        StdRng::seed_from_u64(splitmix64(self.0 ^ splitmix64(chunk_key)))
+    }
+
+    /// A rng for the edge between two neighbouring chunks, the same for the chunks on both sides.
+    /// The edge is identified by the chunk east of it if it is `vertical`, north of it otherwise.
+    pub fn edge_rng(&self, chunk_pos: IVec2, vertical: bool) -> StdRng {
+        const EDGE_SALT: u64 = 0x6564_6765;
+        let edge_key = ((chunk_pos.x as u32 as u64) << 32) | chunk_pos.y as u32 as u64;
+        let salt = if vertical { EDGE_SALT } else { !EDGE_SALT };
+        StdRng::seed_from_u64(splitmix64(self.0 ^ splitmix64(edge_key ^ salt)))
+    }
+}
+
+/// Randomness of everything that happens during a run, like where devils wander.
+/// Derived from the WorldSeed and saved with the game, so replaying a seed or loading a save
+/// plays out the same way.
+#[derive(Resource, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameRng {
+    seed: u64,
+    /// how many rngs were handed out so far
+    draws: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: &WorldSeed) -> Self {
+        const GAME_SALT: u64 = 0x6761_6d65;
+        Self {
+            seed: splitmix64(seed.0 ^ GAME_SALT),
+            draws: 0,
+        }
+    }
+
+    /// A rng for a single decision, every call returns a different one
+    pub fn next_rng(&mut self) -> StdRng {
+        self.draws += 1;
+        StdRng::seed_from_u64(splitmix64(self.seed ^ splitmix64(self.draws)))
+    }
+}
+
+/// Which neighbouring positions an entity can step to.
+/// Shared by player input, AI and pathfinding, so everything moves by the same rules.
+/// Set the `M_ROUGE_MOVEMENT` environment variable to `4`, `8` or `8-no-corners` to choose one.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MovementTopology {
+    /// only orthogonal steps
+    #[default]
+    FourWay,
+    /// orthogonal and diagonal steps, diagonal steps cost the same as orthogonal ones
+    EightWay,
+    /// like EightWay, but diagonal steps need both orthogonal neighbours to be walkable,
+    /// so nothing can squeeze past the corner of a wall
+    EightWayNoCornerCutting,
+}
+
+impl MovementTopology {
+    const ORTHOGONAL: [IVec2; 4] = [IVec2::Y, IVec2::X, IVec2::NEG_Y, IVec2::NEG_X];
+    const ALL: [IVec2; 8] = [
+        IVec2::Y,
+        IVec2::X,
+        IVec2::NEG_Y,
+        IVec2::NEG_X,
+        IVec2::new(1, 1),
+        IVec2::new(1, -1),
+        IVec2::new(-1, -1),
+        IVec2::new(-1, 1),
+    ];
+
+    pub fn from_env() -> Self {
+        match std::env::var("M_ROUGE_MOVEMENT").as_deref() {
+            Ok("8") => Self::EightWay,
+            Ok("8-no-corners") => Self::EightWayNoCornerCutting,
+            _ => Self::FourWay,
+        }
+    }
+
+    /// All directions a single step can go into
+    pub fn directions(&self) -> &'static [IVec2] {
+        match self {
+            Self::FourWay => &Self::ORTHOGONAL,
+            Self::EightWay | Self::EightWayNoCornerCutting => &Self::ALL,
+        }
+    }
+
+    /// Least amount of steps between two positions, ignoring obstacles.
+    /// Manhattan distance for four way movement, Chebyshev distance otherwise.
+    /// As diagonal steps cost the same as orthogonal ones, this never overestimates and is
+    /// used as the A* heuristic.
+    pub fn distance(&self, from: &GridPos, to: &GridPos) -> i32 {
+        match self {
+            Self::FourWay => from.manhattan_distance(to),
+            Self::EightWay | Self::EightWayNoCornerCutting => from.chebyshev_distance(to),
+        }
+    }
+}
+
+/// https://prng.di.unimi.it/splitmix64.c
+/// Used to spread neighbouring chunk positions over the whole seed space
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// The generated content of a single chunk, without any ECS state attached.
+/// Generating the same chunk position with the same seed always yields the same layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkLayout {
+    /// kind of each tile, indexed by `y * CHUNK_SIZE.x + x`
+    pub tiles: Vec<TileKind>,
+    /// grid positions of the devils living in this chunk
+    pub devils: Vec<GridPos>,
+}
+
+impl ChunkLayout {
+    pub fn generate(
+        seed: &WorldSeed,
+        generator: MapGeneratorKind,
+        tiles: &TileRegistry,
+        chunk_pos: IVec2,
+    ) -> Self {
+        const BUSH_CHANCE: f32 = 0.04;
+        const CHASM_CHANCE: f32 = 0.02;
+        const DEVIL_CHANCE: f32 = 0.05;
+        const NEIGHBOURS: [(i32, i32); 8] = [
+            (0, 1),
+            (1, 1),
+            (1, 0),
+            (1, -1),
+            (0, -1),
+            (-1, -1),
+            (-1, 0),
+            (-1, 1),
+        ];
+        let floor = tiles
+            .kind("floor")
+            .expect("tile registry has no \"floor\" tile");
+        let wall = tiles
+            .kind("wall")
+            .expect("tile registry has no \"wall\" tile");
+        // optional decoration, floor is used if they are not defined
+        let bush = tiles.kind("bush").unwrap_or(floor);
+        let chasm = tiles.kind("chasm").unwrap_or(floor);
+        let mut rng = seed.chunk_rng(chunk_pos);
+        let gates = ChunkGates::new(seed, chunk_pos);
+        let mut walls = generator.generator().generate(&mut rng, &gates);
+        // the chunk the player starts in needs a way from the start to its gates
+        let start = (GameGrid::grid_pos_to_chunk_pos(START_POS) == chunk_pos).then(|| {
+            let chunk_origin = GameGrid::tile_pos_to_grid_pos(chunk_pos, TilePos { x: 0, y: 0 });
+            UVec2::new(
+                (START_POS.x - chunk_origin.x) as u32,
+                (START_POS.y - chunk_origin.y) as u32,
+            )
+        });
+        if let Some(start) = start {
+            walls.connect_to_gates(start, &gates);
+        }
+        walls.seal_isolated_regions(&gates);
+        let mut chunk_tiles = vec![floor; (CHUNK_SIZE.x * CHUNK_SIZE.y) as usize];
+        let mut devils = Vec::new();
+
+        for x in 0..CHUNK_SIZE.x {
+            for y in 0..CHUNK_SIZE.y {
+                let (ix, iy) = (x as i32, y as i32);
+                let roll = rng.random::<f32>();
+                let kind = if walls.is_wall(ix, iy) {
+                    wall
+                } else if gates.contains(UVec2::new(x, y)) || start == Some(UVec2::new(x, y)) {
+                    // gates and the start stay plain floor, nothing may block the way from them
+                    floor
+                } else if roll < BUSH_CHANCE {
+                    bush
+                } else if roll < BUSH_CHANCE + CHASM_CHANCE
+                    && NEIGHBOURS.iter().all(|(dx, dy)| {
+                        let (nx, ny) = (ix + dx, iy + dy);
+                        !walls.is_wall(nx, ny)
+                            && chunk_tiles[(ny * CHUNK_SIZE.x as i32 + nx) as usize] != chasm
+                    })
+                {
+                    // a chasm is only placed where every tile around it is walkable, so it can
+                    // always be walked around and never cuts the chunk in two
+                    chasm
+                } else {
+                    floor
+                };
+                chunk_tiles[(y * CHUNK_SIZE.x + x) as usize] = kind;
+
+                // Maybe spawn a devil on walkable tiles, but not on top of the player
+                let pos = GameGrid::tile_pos_to_grid_pos(chunk_pos, TilePos { x, y });
+                if tiles.get(kind).walkable
+                    && rng.random::<f32>() < DEVIL_CHANCE
+                    && pos != START_POS
+                {
+                    devils.push(pos);
+                }
+            }
+        }
+
+        Self {
+            tiles: chunk_tiles,
+            devils,
+        }
+    }
+
+    pub fn tile(&self, tile_pos: TilePos) -> TileKind {
+        self.tiles[(tile_pos.y * CHUNK_SIZE.x + tile_pos.x) as usize]
+    }
 }
 
 pub const TILE_SIZE: TilemapTileSize = TilemapTileSize { x: 16.0, y: 16.0 };
-const CHUNK_SIZE: UVec2 = UVec2 { x: 30, y: 30 };
+pub const CHUNK_SIZE: UVec2 = UVec2 { x: 30, y: 30 };
+/// Where the player starts a run, generation keeps this tile walkable
+pub const START_POS: GridPos = GridPos { x: 0, y: 0 };
 const RENDER_CHUNK_SIZE: UVec2 = UVec2 {
     x: CHUNK_SIZE.x * 2,
     y: CHUNK_SIZE.y * 2,
@@ -32,7 +272,7 @@ const RENDER_CHUNK_SIZE: UVec2 = UVec2 {
 
 // each tile is our world has a Grid Position that can be calculated from a World Position
 // this is a basic building block for pathfinding and fov calculations
-#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Reflect, Serialize, Deserialize)]
 pub struct GridPos {
     pub x: i32,
     pub y: i32,
@@ -61,13 +301,52 @@ impl GridPos {
         }
     }
 
-    pub fn to_world_pos(&self) -> Vec2 {
+    pub fn to_world_pos(self) -> Vec2 {
         Vec2::new(self.x as f32 * TILE_SIZE.x, self.y as f32 * TILE_SIZE.y)
     }
 
     pub fn manhattan_distance(&self, other: &GridPos) -> i32 {
         (self.x - other.x).abs() + (self.y - other.y).abs()
     }
+
+    pub fn chebyshev_distance(&self, other: &GridPos) -> i32 {
+        (self.x - other.x).abs().max((self.y - other.y).abs())
+    }
+
+    pub fn offset(self, direction: IVec2) -> Self {
+        Self {
+            x: self.x + direction.x,
+            y: self.y + direction.y,
+        }
+    }
+}
+
+/// Limits how much work a single path search may do, so far away or unreachable goals
+/// can't stall a frame.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathBudget {
+    /// max amount of positions expanded before the search gives up
+    pub max_nodes: usize,
+    /// positions further away from the start than this are never considered
+    pub max_distance: i32,
+}
+
+impl Default for PathBudget {
+    fn default() -> Self {
+        Self {
+            max_nodes: 4096,
+            max_distance: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathResult {
+    /// positions from the start to the end of the path, both included
+    pub path: Vec<GridPos>,
+    /// false if the goal could not be reached within the budget,
+    /// the path then leads to the reachable position closest to the goal
+    pub complete: bool,
 }
 
 // grid helper struct for pathfinding & position management
@@ -75,125 +354,187 @@ pub struct GameGrid;
 
 impl GameGrid {
     /// Returns whether a position is walkable based on tile's at the position
-    pub fn is_walkable(
-        pos: &GridPos,
-        chunks_query: &Query<(
-            &TileStorage,
-            &TilemapSize,
-            &TilemapGridSize,
-            &TilemapType,
-            &Transform,
-        )>,
-        tile_query: &Query<&TileTextureIndex>,
-    ) -> bool {
-        let world_pos = pos.to_world_pos();
-        chunks_query
-            .iter()
-            .any(|(tile_storage, map_size, grid_size, map_type, transform)| {
-                let pos_in_chunk: Vec2 = {
-                    let pos = Vec4::from((world_pos, 0.0, 1.0));
-                    let pos_in_chunk = transform.compute_matrix().inverse() * pos;
-                    pos_in_chunk.xy()
-                };
+    pub fn is_walkable(pos: &GridPos, world_grid: &WorldGrid, tiles: &TileRegistry) -> bool {
+        world_grid
+            .kind(pos)
+            .is_some_and(|kind| tiles.get(kind).walkable)
+    }
 
-                let Some(tile_pos) =
-                    TilePos::from_world_pos(&pos_in_chunk, map_size, grid_size, map_type)
-                else {
-                    return false;
-                };
+    /// Returns whether the tile at the position can be seen through.
+    /// This is independent of walkability, e.g. chasms are transparent but not walkable.
+    pub fn is_transparent(pos: &GridPos, world_grid: &WorldGrid, tiles: &TileRegistry) -> bool {
+        world_grid
+            .kind(pos)
+            .is_some_and(|kind| tiles.get(kind).transparent)
+    }
 
-                let Some(tile_entity) = tile_storage.get(&tile_pos) else {
-                    return false;
-                };
+    /// Whether an entity at `from` can step to the neighbouring position `to`,
+    /// ignoring other entities
+    pub fn can_step(
+        from: &GridPos,
+        to: &GridPos,
+        topology: MovementTopology,
+        world_grid: &WorldGrid,
+        tiles: &TileRegistry,
+    ) -> bool {
+        let direction = IVec2::new(to.x - from.x, to.y - from.y);
+        if !topology.directions().contains(&direction) {
+            return false;
+        }
 
-                let Ok(texture_index) = tile_query.get(tile_entity) else {
-                    return false;
-                };
+        let is_diagonal = direction.x != 0 && direction.y != 0;
+        if is_diagonal && topology == MovementTopology::EightWayNoCornerCutting {
+            let horizontal = from.offset(IVec2::new(direction.x, 0));
+            let vertical = from.offset(IVec2::new(0, direction.y));
+            if !Self::is_walkable(&horizontal, world_grid, tiles)
+                || !Self::is_walkable(&vertical, world_grid, tiles)
+            {
+                return false;
+            }
+        }
 
-                texture_index.0 == 5 // TODO: Make this configurable
-            })
+        Self::is_walkable(to, world_grid, tiles)
     }
 
     fn get_successors(
         pos: &GridPos,
-        chunks_query: &Query<(
-            &TileStorage,
-            &TilemapSize,
-            &TilemapGridSize,
-            &TilemapType,
-            &Transform,
-        )>,
-        tile_query: &Query<&TileTextureIndex>,
+        goal: &GridPos,
+        topology: MovementTopology,
+        world_grid: &WorldGrid,
+        tiles: &TileRegistry,
+        occupancy: &Occupancy,
     ) -> Vec<(GridPos, i32)> {
-        let directions = [(0, 1), (1, 0), (0, -1), (-1, 0)];
-
-        directions
+        topology
+            .directions()
             .iter()
-            .map(|&(dx, dy)| GridPos {
-                x: pos.x + dx,
-                y: pos.y + dy,
+            .map(|direction| pos.offset(*direction))
+            // route around other creatures, but allow reaching one at the goal
+            .filter(|next| next == goal || !occupancy.is_occupied(next))
+            .filter(|next| Self::can_step(pos, next, topology, world_grid, tiles))
+            .filter_map(|next| {
+                let tile = tiles.get(world_grid.kind(&next)?);
+                Some((next, tile.movement_cost as i32))
             })
-            .filter(|pos| Self::is_walkable(pos, chunks_query, tile_query))
-            .map(|pos| (pos, 1))
             .collect()
     }
 
-    /// Find a path between two world positions
-    pub fn find_path(
-        chunks_query: &Query<(
-            &TileStorage,
-            &TilemapSize,
-            &TilemapGridSize,
-            &TilemapType,
-            &Transform,
-        )>,
-        tile_query: &Query<&TileTextureIndex>,
-        from: Vec2,
-        to: Vec2,
-    ) -> Option<Vec<Vec2>> {
-        let start = GridPos::from_world_pos(from);
-        let goal = GridPos::from_world_pos(to);
-
-        let result = astar(
-            &start,
-            |p| Self::get_successors(p, chunks_query, tile_query),
-            |p| p.manhattan_distance(&goal),
-            |p| p == &goal,
-        );
+    /// A* search between two grid positions, avoiding occupied positions on the way.
+    /// If the goal can't be reached within the budget, the path leads to the closest reachable
+    /// position instead. Returns None if there is no way to get any closer to the goal.
+    /// Use the PathCache instead of calling this every frame.
+    pub fn find_grid_path(
+        world_grid: &WorldGrid,
+        tiles: &TileRegistry,
+        occupancy: &Occupancy,
+        topology: MovementTopology,
+        budget: &PathBudget,
+        start: GridPos,
+        goal: GridPos,
+    ) -> Option<PathResult> {
+        let mut open = BinaryHeap::new();
+        let mut costs: HashMap<GridPos, i32> = HashMap::new();
+        let mut came_from: HashMap<GridPos, GridPos> = HashMap::new();
+
+        let start_heuristic = topology.distance(&start, &goal);
+        open.push(Reverse((
+            start_heuristic,
+            start_heuristic,
+            0,
+            start.x,
+            start.y,
+        )));
+        costs.insert(start, 0);
+
+        // closest position to the goal by (heuristic, cost)
+        let mut closest = (start_heuristic, 0, start);
+        let mut expanded = 0;
+
+        while let Some(Reverse((_, heuristic, cost, x, y))) = open.pop() {
+            let pos = GridPos { x, y };
+            if costs.get(&pos).is_some_and(|best| *best < cost) {
+                // a cheaper way to this position was found after this one was queued
+                continue;
+            }
+
+            if pos == goal {
+                return Some(PathResult {
+                    path: Self::reconstruct_path(&came_from, goal),
+                    complete: true,
+                });
+            }
+
+            if (heuristic, cost) < (closest.0, closest.1) {
+                closest = (heuristic, cost, pos);
+            }
+
+            expanded += 1;
+            if expanded > budget.max_nodes {
+                break;
+            }
+
+            for (next, step_cost) in
+                Self::get_successors(&pos, &goal, topology, world_grid, tiles, occupancy)
+            {
+                if topology.distance(&next, &start) > budget.max_distance {
+                    continue;
+                }
+
+                let next_cost = cost + step_cost;
+                if costs.get(&next).is_some_and(|best| *best <= next_cost) {
+                    continue;
+                }
 
-        result.map(|(path, _cost)| {
-            path.into_iter()
-                .map(|grid_pos| grid_pos.to_world_pos())
-                .collect()
+                costs.insert(next, next_cost);
+                came_from.insert(next, pos);
+                let next_heuristic = topology.distance(&next, &goal);
+                open.push(Reverse((
+                    next_cost + next_heuristic,
+                    next_heuristic,
+                    next_cost,
+                    next.x,
+                    next.y,
+                )));
+            }
+        }
+
+        let (_, _, closest_pos) = closest;
+        (closest_pos != start).then(|| PathResult {
+            path: Self::reconstruct_path(&came_from, closest_pos),
+            complete: false,
         })
     }
 
+    fn reconstruct_path(came_from: &HashMap<GridPos, GridPos>, end: GridPos) -> Vec<GridPos> {
+        let mut path = vec![end];
+        let mut current = end;
+        while let Some(previous) = came_from.get(&current) {
+            path.push(*previous);
+            current = *previous;
+        }
+        path.reverse();
+        path
+    }
+
     /// TODO: dont know about this...
     /// Cast a ray from one grid position to another, returning all positions along the ray
     /// Including the start and end positions
     /// https://en.wikipedia.org/wiki/Bresenham%27s_line_algorithm
     pub fn raycast(from: GridPos, to: GridPos) -> Bresenham {
-        return Bresenham::new(from.into(), to.into());
+        Bresenham::new(from.into(), to.into())
     }
 
-    /// Cast a ray and return the first non-walkable position, if any
+    /// Cast a ray and return the first position that blocks sight, if any
     /// Returns None if the ray reaches the target without hitting anything
     pub fn raycast_hit(
         from: GridPos,
         to: GridPos,
-        chunks_query: &Query<(
-            &TileStorage,
-            &TilemapSize,
-            &TilemapGridSize,
-            &TilemapType,
-            &Transform,
-        )>,
-        tile_query: &Query<&TileTextureIndex>,
+        world_grid: &WorldGrid,
+        tiles: &TileRegistry,
     ) -> Option<GridPos> {
         let positions = Self::raycast(from, to);
 
         for pos in positions {
-            if !Self::is_walkable(&pos.into(), chunks_query, tile_query) {
+            if !Self::is_transparent(&pos.into(), world_grid, tiles) {
                 return Some(pos.into());
             }
         }
@@ -205,20 +546,14 @@ impl GameGrid {
     pub fn has_line_of_sight(
         from: GridPos,
         to: GridPos,
-        chunks_query: &Query<(
-            &TileStorage,
-            &TilemapSize,
-            &TilemapGridSize,
-            &TilemapType,
-            &Transform,
-        )>,
-        tile_query: &Query<&TileTextureIndex>,
+        world_grid: &WorldGrid,
+        tiles: &TileRegistry,
     ) -> bool {
         if from == to {
             return true;
         }
 
-        let hit = Self::raycast_hit(from, to, chunks_query, tile_query);
+        let hit = Self::raycast_hit(from, to, world_grid, tiles);
 
         // We have line of sight if we either:
         // 1. Hit nothing (ray reached target)
@@ -236,25 +571,64 @@ impl GameGrid {
             0.0,
         )
     }
+
+    /// Position of the chunk that contains the grid position
+    pub fn grid_pos_to_chunk_pos(pos: GridPos) -> IVec2 {
+        IVec2::new(
+            pos.x.div_euclid(CHUNK_SIZE.x as i32),
+            pos.y.div_euclid(CHUNK_SIZE.y as i32),
+        )
+    }
+
+    /// Grid position of a tile inside the chunk at `chunk_pos`
+    pub fn tile_pos_to_grid_pos(chunk_pos: IVec2, tile_pos: TilePos) -> GridPos {
+        GridPos {
+            x: chunk_pos.x * CHUNK_SIZE.x as i32 + tile_pos.x as i32,
+            y: chunk_pos.y * CHUNK_SIZE.y as i32 + tile_pos.y as i32,
+        }
+    }
 }
 
 pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<WorldSeed>()
+        .init_resource::<ChunkManager>()
+        .init_resource::<GameRng>()
+        .init_resource::<PathBudget>()
+        .insert_resource(MovementTopology::from_env())
+        .init_resource::<SelectedGenerator>()
+        .init_resource::<MapGeneratorKind>()
+        .add_systems(
+            OnEnter(Screen::Gameplay),
+            (start_run, log_world_seed).chain(),
+        )
+        .add_systems(
+            Update,
+            (
+                spawn_chunks_around_player.run_if(resource_exists::<TileRegistry>),
+                despawn_out_of_range_chunks,
+            ),
+        );
+}
+
+pub(super) fn presentation_plugin(app: &mut App) {
     app.add_plugins(TilemapPlugin)
-        .insert_resource(ChunkManager::default())
         .insert_resource(HoveredTilePos(None))
         .add_systems(
             Update,
             (
-                spawn_chunks_around_player,
-                despawn_out_of_range_chunks,
+                add_chunk_rendering,
                 update_cursor_position,
-                draw_path_to_hovered_tile,
+                draw_path_to_hovered_tile.run_if(resource_exists::<TileRegistry>),
                 highlight_hovered_tile,
                 animate_grid_movement,
             ),
         );
 }
 
+pub(super) fn headless_plugin(app: &mut App) {
+    app.add_systems(Update, snap_grid_movement);
+}
+
 fn get_chunk_positions_around(center: IVec2) -> HashSet<IVec2> {
     let mut chunks = HashSet::new();
     for y in (center.y - 2)..(center.y + 2) {
@@ -265,12 +639,37 @@ fn get_chunk_positions_around(center: IVec2) -> HashSet<IVec2> {
     chunks
 }
 
-fn spawn_chunk(commands: &mut Commands, asset_server: &AssetServer, chunk_pos: IVec2) {
+fn log_world_seed(seed: Res<WorldSeed>, generator: Res<MapGeneratorKind>) {
+    info!(
+        "world seed: {} (replay with M_ROUGE_SEED={})",
+        seed.0, seed.0
+    );
+    info!("map generator: {:?}", *generator);
+}
+
+/// Everything derived from the seed starts over with a new run
+fn start_run(
+    seed: Res<WorldSeed>,
+    selected_generator: Res<SelectedGenerator>,
+    mut generator: ResMut<MapGeneratorKind>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut game_rng: ResMut<GameRng>,
+) {
+    *generator = selected_generator.0;
+    chunk_manager.start_run(&seed);
+    *game_rng = GameRng::new(&seed);
+}
+
+fn spawn_chunk(
+    commands: &mut Commands,
+    tiles: &TileRegistry,
+    world_grid: &mut WorldGrid,
+    occupancy: &mut Occupancy,
+    chunk_pos: IVec2,
+    mut chunk: StoredChunk,
+) {
     let tilemap_entity = commands.spawn_empty().id();
     let mut tile_storage = TileStorage::empty(CHUNK_SIZE.into());
-    const OBSTACLE_CHANCE: f32 = 0.2;
-    const DEVIL_CHANCE: f32 = 0.05;
-    let mut rng = rand::rng();
 
     let chunk_world_pos = GameGrid::chunk_pos_to_world_pos(chunk_pos);
 
@@ -278,66 +677,118 @@ fn spawn_chunk(commands: &mut Commands, asset_server: &AssetServer, chunk_pos: I
     for x in 0..CHUNK_SIZE.x {
         for y in 0..CHUNK_SIZE.y {
             let tile_pos = TilePos { x, y };
-            let is_obstacle = rng.random::<f32>() < OBSTACLE_CHANCE;
-            let texture_index = if is_obstacle { 52 } else { 5 };
+            let kind = chunk.tile(x, y);
+            let tile = tiles.get(kind);
 
             let tile_entity = commands
                 .spawn(TileBundle {
                     position: tile_pos,
                     tilemap_id: TilemapId(tilemap_entity),
-                    texture_index: TileTextureIndex(texture_index),
+                    texture_index: TileTextureIndex(tile.atlas_index),
                     visible: TileVisible(false), // INFO: TileVisibility will be set in fog_of_war
                     ..Default::default()
                 })
-                .insert(Name::new(if is_obstacle { "Wall" } else { "Floor" }))
+                .insert((kind, Name::new(tile.name.clone())))
                 .id();
             commands.entity(tilemap_entity).add_child(tile_entity);
             tile_storage.set(&tile_pos, tile_entity);
+            world_grid.insert(
+                GameGrid::tile_pos_to_grid_pos(chunk_pos, tile_pos),
+                WorldTile {
+                    chunk: tilemap_entity,
+                    tile_pos,
+                    entity: tile_entity,
+                    kind,
+                },
+            );
+        }
+    }
 
-            // Maybe spawn a devil on non-obstacle tiles
-            if !is_obstacle && rng.random::<f32>() < DEVIL_CHANCE {
-                let tile_world_pos = Vec3::new(
-                    chunk_world_pos.x + x as f32 * TILE_SIZE.x,
-                    chunk_world_pos.y + y as f32 * TILE_SIZE.y,
-                    chunk_world_pos.z,
-                );
-                spawn_devil(commands, asset_server, tile_world_pos);
-            }
+    for actor in std::mem::take(&mut chunk.actors) {
+        // never spawn on top of the player or another actor, move aside instead
+        let pos = if occupancy.is_occupied(&actor.pos) {
+            let Some(pos) = free_spawn_pos(chunk_pos, &chunk, actor.pos, tiles, occupancy) else {
+                warn!("no free position for {:?} in chunk {chunk_pos}", actor.kind);
+                continue;
+            };
+            pos
+        } else {
+            actor.pos
+        };
+
+        let world_pos = pos.to_world_pos().extend(chunk_world_pos.z);
+        let actor_entity = match actor.kind {
+            ActorKind::Devil => spawn_devil(commands, world_pos),
+        };
+        commands.entity(actor_entity).insert(actor.turn_taker);
+        if let Some(health) = actor.health {
+            commands.entity(actor_entity).insert(health);
         }
+        occupancy.place(actor_entity, pos, None);
     }
 
-    let transform = Transform::from_translation(chunk_world_pos);
-    let texture_atlas = asset_server.load("images/atlas.png");
-    let mut entity = commands.entity(tilemap_entity);
+    // INFO: the components needed for rendering are added in add_chunk_rendering
+    commands.entity(tilemap_entity).insert((
+        Name::new("Chunk"),
+        StateScoped(Screen::Gameplay),
+        tile_storage,
+        TilemapSize::from(CHUNK_SIZE),
+        TilemapGridSize::from(TILE_SIZE),
+        TilemapType::Square,
+        Transform::from_translation(chunk_world_pos),
+    ));
+}
+
+/// The free walkable position of a chunk closest to `pos`
+fn free_spawn_pos(
+    chunk_pos: IVec2,
+    chunk: &StoredChunk,
+    pos: GridPos,
+    tiles: &TileRegistry,
+    occupancy: &Occupancy,
+) -> Option<GridPos> {
+    (0..CHUNK_SIZE.x)
+        .flat_map(|x| (0..CHUNK_SIZE.y).map(move |y| TilePos { x, y }))
+        .filter(|tile_pos| tiles.get(chunk.tile(tile_pos.x, tile_pos.y)).walkable)
+        .map(|tile_pos| GameGrid::tile_pos_to_grid_pos(chunk_pos, tile_pos))
+        .filter(|candidate| !occupancy.is_occupied(candidate))
+        .min_by_key(|candidate| (candidate.x - pos.x).pow(2) + (candidate.y - pos.y).pow(2))
+}
 
-    entity
-        .insert(TilemapBundle {
+fn add_chunk_rendering(
+    mut commands: Commands,
+    chunks_query: Query<(Entity, &TileStorage, &Transform), Added<TileStorage>>,
+    assets: Res<GameAssets>,
+) {
+    for (entity, tile_storage, transform) in chunks_query.iter() {
+        commands.entity(entity).insert(TilemapBundle {
             grid_size: TILE_SIZE.into(),
             size: CHUNK_SIZE.into(),
-            storage: tile_storage,
-            texture: TilemapTexture::Single(texture_atlas),
+            storage: tile_storage.clone(),
+            texture: TilemapTexture::Single(assets.atlas.clone()),
             tile_size: TILE_SIZE,
-            transform,
+            transform: *transform,
             render_settings: TilemapRenderSettings {
                 render_chunk_size: RENDER_CHUNK_SIZE,
                 ..Default::default()
             },
             ..Default::default()
-        })
-        .insert(Name::new("Chunk"));
+        });
+    }
 }
 
 fn pos_to_chunk_pos(pos: &Vec2) -> IVec2 {
-    let ipos = pos.as_ivec2();
-    let chunk_size: IVec2 = IVec2::new(CHUNK_SIZE.x as i32, CHUNK_SIZE.y as i32);
-    let tile_size: IVec2 = IVec2::new(TILE_SIZE.x as i32, TILE_SIZE.y as i32);
-    ipos / (chunk_size * tile_size)
+    GameGrid::grid_pos_to_chunk_pos(GridPos::from_world_pos(*pos))
 }
 
 fn spawn_chunks_around_player(
     mut commands: Commands,
     player_query: Query<&Transform, With<Player>>,
-    asset_server: Res<AssetServer>,
+    seed: Res<WorldSeed>,
+    generator: Res<MapGeneratorKind>,
+    tiles: Res<TileRegistry>,
+    mut world_grid: ResMut<WorldGrid>,
+    mut occupancy: ResMut<Occupancy>,
     mut chunk_manager: ResMut<ChunkManager>,
 ) {
     let Ok(player) = player_query.get_single() else {
@@ -351,15 +802,30 @@ fn spawn_chunks_around_player(
     for chunk_pos in chunks {
         if !chunk_manager.spawned_chunks.contains(&chunk_pos) {
             chunk_manager.spawned_chunks.insert(chunk_pos);
-            spawn_chunk(&mut commands, &asset_server, chunk_pos);
+            // restore the chunk as it was left, or generate it for the first time
+            let chunk = chunk_manager.take(chunk_pos).unwrap_or_else(|| {
+                ChunkLayout::generate(&seed, *generator, &tiles, chunk_pos).into()
+            });
+            spawn_chunk(
+                &mut commands,
+                &tiles,
+                &mut world_grid,
+                &mut occupancy,
+                chunk_pos,
+                chunk,
+            );
         }
     }
 }
 
+/// Despawns chunks that left the range around the player, together with the actors inside them.
+/// Their state is kept in the ChunkManager, so they can be restored later.
 fn despawn_out_of_range_chunks(
-    chunks_query: Query<(Entity, &Transform)>,
+    chunks_query: Query<(Entity, &Transform), With<TileStorage>>,
+    actors_query: Query<(Entity, &GridMovement, &TurnTaker, Option<&Health>), With<Devil>>,
     player_query: Query<&Transform, With<Player>>,
     mut commands: Commands,
+    mut world_grid: ResMut<WorldGrid>,
     mut chunk_manager: ResMut<ChunkManager>,
 ) {
     let Ok(player) = player_query.get_single() else {
@@ -371,11 +837,65 @@ fn despawn_out_of_range_chunks(
 
     for (entity, chunk_transform) in chunks_query.iter() {
         let chunk_pos = pos_to_chunk_pos(&chunk_transform.translation.xy());
-        if !valid_chunks.contains(&chunk_pos) {
-            chunk_manager.spawned_chunks.remove(&chunk_pos);
-            commands.entity(entity).despawn_recursive();
+        if valid_chunks.contains(&chunk_pos) {
+            continue;
         }
+
+        // the chunk's tiles are not spawned yet
+        let Some((chunk, actors)) = collect_chunk(chunk_pos, &world_grid, actors_query.iter())
+        else {
+            continue;
+        };
+
+        for actor_entity in actors {
+            commands.entity(actor_entity).despawn_recursive();
+        }
+
+        chunk_manager.spawned_chunks.remove(&chunk_pos);
+        chunk_manager.store(chunk_pos, chunk);
+        world_grid.remove_chunk(chunk_pos);
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// The current state of a spawned chunk together with the actors inside it.
+/// Returns None if the chunk's tiles are not spawned yet.
+pub(super) fn collect_chunk<'a>(
+    chunk_pos: IVec2,
+    world_grid: &WorldGrid,
+    actors: impl Iterator<Item = (Entity, &'a GridMovement, &'a TurnTaker, Option<&'a Health>)>,
+) -> Option<(StoredChunk, Vec<Entity>)> {
+    let tiles: Vec<TileKind> = (0..CHUNK_SIZE.y)
+        .flat_map(|y| (0..CHUNK_SIZE.x).map(move |x| TilePos { x, y }))
+        .map(|tile_pos| world_grid.kind(&GameGrid::tile_pos_to_grid_pos(chunk_pos, tile_pos)))
+        .collect::<Option<_>>()?;
+
+    let mut stored_actors = Vec::new();
+    let mut entities = Vec::new();
+    for (actor_entity, grid_movement, turn_taker, health) in actors {
+        let pos = grid_movement
+            .target_pos
+            .unwrap_or(grid_movement.current_pos);
+        if GameGrid::grid_pos_to_chunk_pos(pos) != chunk_pos {
+            continue;
+        }
+
+        stored_actors.push(StoredActor {
+            kind: ActorKind::Devil,
+            pos,
+            turn_taker: turn_taker.clone(),
+            health: health.copied(),
+        });
+        entities.push(actor_entity);
     }
+
+    Some((
+        StoredChunk {
+            tiles,
+            actors: stored_actors,
+        },
+        entities,
+    ))
 }
 
 fn update_cursor_position(
@@ -446,21 +966,17 @@ fn highlight_hovered_tile(hovered_tile_pos: Res<HoveredTilePos>, mut gizmos: Giz
 }
 
 pub fn draw_path_to_hovered_tile(
-    chunks_query: Query<(
-        &TileStorage,
-        &TilemapSize,
-        &TilemapGridSize,
-        &TilemapType,
-        &Transform,
-    )>,
-    tile_query: Query<&TileTextureIndex>,
-    player_query: Query<&Transform, With<Player>>,
+    world_grid: Res<WorldGrid>,
+    tiles: Res<TileRegistry>,
+    occupancy: Res<Occupancy>,
+    topology: Res<MovementTopology>,
+    budget: Res<PathBudget>,
+    mut path_cache: ResMut<PathCache>,
+    player_query: Query<&GridMovement, With<Player>>,
     hovered_tile_pos: Res<HoveredTilePos>,
     mut gizmos: Gizmos,
 ) {
-    let player_pos = if let Ok(transform) = player_query.get_single() {
-        transform.translation.xy()
-    } else {
+    let Ok(grid_movement) = player_query.get_single() else {
         return;
     };
 
@@ -468,26 +984,34 @@ pub fn draw_path_to_hovered_tile(
         return;
     };
 
-    let Some(path_to_target) =
-        GameGrid::find_path(&chunks_query, &tile_query, player_pos, target_pos)
-    else {
+    let Some(path_to_target) = path_cache.find_path(
+        &world_grid,
+        &tiles,
+        &occupancy,
+        *topology,
+        &budget,
+        grid_movement.current_pos,
+        GridPos::from_world_pos(target_pos),
+    ) else {
         return;
     };
 
+    // partial paths only get close to the target
+    let color = if path_to_target.complete {
+        Color::srgba(22.0, 101.0, 52.0, 1.0)
+    } else {
+        Color::srgba(1.0, 0.6, 0.0, 1.0)
+    };
+
     // Draw path
-    let points: Vec<Vec3> = path_to_target
+    let points: Vec<Vec2> = path_to_target
+        .path
         .iter()
-        .map(|pos| Vec3::new(pos.x, pos.y, 1.0))
+        .map(|pos| pos.to_world_pos())
         .collect();
 
-    if points.len() >= 2 {
-        for points in points.windows(2) {
-            gizmos.line_2d(
-                points[0].xy(),
-                points[1].xy(),
-                Color::srgba(22.0, 101.0, 52.0, 1.0),
-            );
-        }
+    for points in points.windows(2) {
+        gizmos.line_2d(points[0], points[1], color);
     }
 }
 
@@ -513,3 +1037,184 @@ fn animate_grid_movement(mut query: Query<(&mut Transform, &mut GridMovement)>,
         }
     }
 }
+
+// without anything to look at, movement finishes immediately
+fn snap_grid_movement(mut query: Query<(&mut Transform, &mut GridMovement)>) {
+    for (mut transform, mut movement) in &mut query {
+        let Some(target) = movement.target_pos else {
+            continue;
+        };
+
+        let target_world_pos = target.to_world_pos();
+        transform.translation.x = target_world_pos.x;
+        transform.translation.y = target_world_pos.y;
+        movement.current_pos = target;
+        movement.target_pos = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::tiles::TileDefinitions;
+
+    fn tile_registry() -> TileRegistry {
+        let definitions: TileDefinitions =
+            ron::from_str(include_str!("../../assets/data/map.tiles.ron")).unwrap();
+        TileRegistry::new(definitions.tiles)
+    }
+
+    /// One character per tile, the top row first
+    fn render(layout: &ChunkLayout, tiles: &TileRegistry) -> Vec<String> {
+        (0..CHUNK_SIZE.y)
+            .rev()
+            .map(|y| {
+                (0..CHUNK_SIZE.x)
+                    .map(
+                        |x| match tiles.get(layout.tile(TilePos { x, y })).id.as_str() {
+                            "wall" => '#',
+                            "bush" => '*',
+                            "chasm" => '~',
+                            _ => '.',
+                        },
+                    )
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chunk_generation_is_deterministic() {
+        let tiles = tile_registry();
+        let seed = WorldSeed(42);
+
+        for chunk_pos in [IVec2::ZERO, IVec2::new(-3, 7)] {
+            let first =
+                ChunkLayout::generate(&seed, MapGeneratorKind::default(), &tiles, chunk_pos);
+            let second =
+                ChunkLayout::generate(&seed, MapGeneratorKind::default(), &tiles, chunk_pos);
+            assert_eq!(first, second);
+        }
+
+        let other_chunk =
+            ChunkLayout::generate(&seed, MapGeneratorKind::default(), &tiles, IVec2::X);
+        let other_seed = ChunkLayout::generate(
+            &WorldSeed(43),
+            MapGeneratorKind::default(),
+            &tiles,
+            IVec2::ZERO,
+        );
+        let layout = ChunkLayout::generate(&seed, MapGeneratorKind::default(), &tiles, IVec2::ZERO);
+        assert_ne!(layout, other_chunk);
+        assert_ne!(layout, other_seed);
+    }
+
+    #[test]
+    fn chunk_generation_matches_known_layout() {
+        let tiles = tile_registry();
+        let layout = ChunkLayout::generate(
+            &WorldSeed(42),
+            MapGeneratorKind::RoomsAndCorridors,
+            &tiles,
+            IVec2::ZERO,
+        );
+
+        // update this when the generator is changed on purpose
+        let expected = [
+            "#########.####################",
+            "#########.####################",
+            "#########.####################",
+            "#########.####################",
+            "#########.####################",
+            "####.......###################",
+            "####....~..###################",
+            "####.......#.....########....#",
+            "####.............##.....#....#",
+            "####.............##.*...#..~.#",
+            "####.......**...........#....#",
+            "####.............##..........#",
+            "####.............##.....#....#",
+            "#######.###......##.....#....#",
+            "#######.###.#######..*.*#....#",
+            "#######........######.#.######",
+            "#######...............#.######",
+            "#######.....~..########.######",
+            "#######........########.######",
+            "#######.###.#######........###",
+            "#######.###*#######........###",
+            "#######.###.#######*.......###",
+            "#######..........*............",
+            "###########.#######.....*..###",
+            "###########.#######........###",
+            "###########.#######.**....*###",
+            "###########.#######........###",
+            "............#######.##########",
+            ".##################.##########",
+            ".##################.##########",
+        ];
+        assert_eq!(render(&layout, &tiles), expected);
+
+        let devils: Vec<(i32, i32)> = layout.devils.iter().map(|pos| (pos.x, pos.y)).collect();
+        assert_eq!(
+            devils,
+            [
+                (6, 18),
+                (6, 19),
+                (7, 19),
+                (7, 23),
+                (9, 18),
+                (10, 12),
+                (10, 22),
+                (11, 17),
+                (11, 20),
+                (13, 11),
+                (18, 19),
+                (20, 6),
+                (21, 14),
+                (22, 6),
+                (24, 3),
+                (25, 7),
+                (28, 16),
+            ]
+        );
+    }
+
+    #[test]
+    fn occupied_actors_spawn_next_to_their_position() {
+        let tiles = tile_registry();
+        let floor = tiles.kind("floor").unwrap();
+        let wall = tiles.kind("wall").unwrap();
+        let mut chunk = StoredChunk {
+            tiles: vec![floor; (CHUNK_SIZE.x * CHUNK_SIZE.y) as usize],
+            actors: Vec::new(),
+        };
+        // walls to the left, right and below, only above is free
+        for (x, y) in [(4, 5), (6, 5), (5, 4)] {
+            chunk.tiles[(y * CHUNK_SIZE.x + x) as usize] = wall;
+        }
+        let chunk_pos = IVec2::new(2, -1);
+        let pos = GameGrid::tile_pos_to_grid_pos(chunk_pos, TilePos { x: 5, y: 5 });
+        let mut occupancy = Occupancy::default();
+        occupancy.place(Entity::from_raw(1), pos, None);
+
+        let spawn_pos = free_spawn_pos(chunk_pos, &chunk, pos, &tiles, &occupancy);
+
+        assert_eq!(spawn_pos, Some(pos.offset(IVec2::Y)));
+    }
+
+    #[test]
+    fn game_rng_follows_the_seed() {
+        let draw = |game_rng: &mut GameRng| game_rng.next_rng().random::<u64>();
+        let mut first = GameRng::new(&WorldSeed(42));
+        let mut second = GameRng::new(&WorldSeed(42));
+
+        let draws: Vec<u64> = (0..4).map(|_| draw(&mut first)).collect();
+        assert_eq!(draws, (0..4).map(|_| draw(&mut second)).collect::<Vec<_>>());
+        assert_ne!(draws[0], draws[1]);
+
+        // a loaded rng continues where the saved one left off
+        let mut loaded = first.clone();
+        assert_eq!(draw(&mut loaded), draw(&mut first));
+        assert_ne!(draw(&mut GameRng::new(&WorldSeed(43))), draws[0]);
+    }
+}
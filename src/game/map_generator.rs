@@ -0,0 +1,494 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashSet};
+use rand::{Rng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    map::{CHUNK_SIZE, GameGrid, GridPos, WorldSeed},
+    tiles::TileRegistry,
+    world_grid::WorldGrid,
+};
+
+const WIDTH: i32 = CHUNK_SIZE.x as i32;
+const HEIGHT: i32 = CHUNK_SIZE.y as i32;
+
+/// Which MapGenerator lays out the chunks of the current run.
+/// Set to the SelectedGenerator when a run starts, loading a save restores the saved one.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MapGeneratorKind {
+    /// scattered walls without any structure
+    Noise,
+    #[default]
+    RoomsAndCorridors,
+    Caves,
+    Bsp,
+}
+
+impl MapGeneratorKind {
+    pub fn from_env() -> Self {
+        match std::env::var("M_ROUGE_GENERATOR").as_deref() {
+            Ok("noise") => Self::Noise,
+            Ok("caves") => Self::Caves,
+            Ok("bsp") => Self::Bsp,
+            _ => Self::RoomsAndCorridors,
+        }
+    }
+
+    pub fn generator(&self) -> &'static dyn MapGenerator {
+        match self {
+            Self::Noise => &NoiseGenerator,
+            Self::RoomsAndCorridors => &RoomsAndCorridors,
+            Self::Caves => &Caves,
+            Self::Bsp => &Bsp,
+        }
+    }
+}
+
+/// Which MapGenerator new runs use.
+/// Insert it before adding the game's plugins to choose one, otherwise it is read from the
+/// `M_ROUGE_GENERATOR` environment variable: `noise`, `rooms`, `caves` or `bsp`.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SelectedGenerator(pub MapGeneratorKind);
+
+impl Default for SelectedGenerator {
+    fn default() -> Self {
+        Self(MapGeneratorKind::from_env())
+    }
+}
+
+/// Lays out the walls of a single chunk.
+/// Every gate has to be walkable and connected to the other gates, so the walkable areas of
+/// neighbouring chunks are connected through them.
+pub trait MapGenerator: Send + Sync {
+    /// Must only use `rng` for randomness, so a chunk comes out the same for the same seed.
+    fn generate(&self, rng: &mut StdRng, gates: &ChunkGates) -> WallGrid;
+}
+
+/// Where a chunk's walkable area meets its neighbours', one tile on each edge.
+/// The two chunks on either side of an edge derive the same gate position from the WorldSeed,
+/// so their gate tiles end up right next to each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkGates {
+    pub north: UVec2,
+    pub south: UVec2,
+    pub east: UVec2,
+    pub west: UVec2,
+}
+
+impl ChunkGates {
+    pub fn new(seed: &WorldSeed, chunk_pos: IVec2) -> Self {
+        let west = gate_offset(seed.edge_rng(chunk_pos, true), CHUNK_SIZE.y);
+        let east = gate_offset(seed.edge_rng(chunk_pos + IVec2::X, true), CHUNK_SIZE.y);
+        let south = gate_offset(seed.edge_rng(chunk_pos, false), CHUNK_SIZE.x);
+        let north = gate_offset(seed.edge_rng(chunk_pos + IVec2::Y, false), CHUNK_SIZE.x);
+
+        Self {
+            north: UVec2::new(north, CHUNK_SIZE.y - 1),
+            south: UVec2::new(south, 0),
+            east: UVec2::new(CHUNK_SIZE.x - 1, east),
+            west: UVec2::new(0, west),
+        }
+    }
+
+    pub fn all(&self) -> [UVec2; 4] {
+        [self.north, self.south, self.east, self.west]
+    }
+
+    pub fn contains(&self, tile: UVec2) -> bool {
+        self.all().contains(&tile)
+    }
+}
+
+/// Position of a gate along an edge of `len` tiles, away from the corners
+fn gate_offset(mut rng: StdRng, len: u32) -> u32 {
+    rng.random_range(2..len - 2)
+}
+
+/// Which tiles of a chunk are walls, as laid out by a MapGenerator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WallGrid {
+    /// indexed by `y * CHUNK_SIZE.x + x`
+    walls: Vec<bool>,
+}
+
+impl WallGrid {
+    pub fn filled() -> Self {
+        Self {
+            walls: vec![true; (CHUNK_SIZE.x * CHUNK_SIZE.y) as usize],
+        }
+    }
+
+    pub fn open() -> Self {
+        Self {
+            walls: vec![false; (CHUNK_SIZE.x * CHUNK_SIZE.y) as usize],
+        }
+    }
+
+    /// Tiles outside the chunk count as walls
+    pub fn is_wall(&self, x: i32, y: i32) -> bool {
+        if !(0..WIDTH).contains(&x) || !(0..HEIGHT).contains(&y) {
+            return true;
+        }
+        self.walls[(y * WIDTH + x) as usize]
+    }
+
+    pub fn set_wall(&mut self, x: i32, y: i32, wall: bool) {
+        if (0..WIDTH).contains(&x) && (0..HEIGHT).contains(&y) {
+            self.walls[(y * WIDTH + x) as usize] = wall;
+        }
+    }
+
+    pub fn carve_room(&mut self, room: &Room) {
+        for y in room.min.y..room.max.y {
+            for x in room.min.x..room.max.x {
+                self.set_wall(x as i32, y as i32, false);
+            }
+        }
+    }
+
+    /// Carve an L shaped corridor between two tiles, horizontally first
+    pub fn carve_corridor(&mut self, from: UVec2, to: UVec2) {
+        let (from, to) = (from.as_ivec2(), to.as_ivec2());
+        for x in from.x.min(to.x)..=from.x.max(to.x) {
+            self.set_wall(x, from.y, false);
+        }
+        for y in from.y.min(to.y)..=from.y.max(to.y) {
+            self.set_wall(to.x, y, false);
+        }
+    }
+
+    /// Carve a corridor from `tile` to the closest gate, so it is connected to the rest of the
+    /// world no matter what the generator left there
+    pub fn connect_to_gates(&mut self, tile: UVec2, gates: &ChunkGates) {
+        let closest = gates
+            .all()
+            .into_iter()
+            .min_by_key(|gate| gate.as_ivec2().distance_squared(tile.as_ivec2()));
+        if let Some(gate) = closest {
+            self.carve_corridor(tile, gate);
+        }
+    }
+
+    /// Wall up every open tile that can't be reached from the gates.
+    /// Afterwards the whole walkable area of the chunk is connected to its neighbours.
+    pub fn seal_isolated_regions(&mut self, gates: &ChunkGates) {
+        let mut reached = vec![false; self.walls.len()];
+        let mut queue = VecDeque::new();
+
+        for gate in gates.all() {
+            let (x, y) = (gate.x as i32, gate.y as i32);
+            self.set_wall(x, y, false);
+            reached[(y * WIDTH + x) as usize] = true;
+            queue.push_back((x, y));
+        }
+
+        // orthogonal steps only, what is connected this way is connected for every
+        // MovementTopology
+        while let Some((x, y)) = queue.pop_front() {
+            for (dx, dy) in [(0, 1), (1, 0), (0, -1), (-1, 0)] {
+                let (nx, ny) = (x + dx, y + dy);
+                if self.is_wall(nx, ny) || reached[(ny * WIDTH + nx) as usize] {
+                    continue;
+                }
+                reached[(ny * WIDTH + nx) as usize] = true;
+                queue.push_back((nx, ny));
+            }
+        }
+
+        for (wall, reached) in self.walls.iter_mut().zip(reached) {
+            *wall |= !reached;
+        }
+    }
+
+    /// Amount of walls around a tile, tiles outside the chunk included
+    fn walls_around(&self, x: i32, y: i32) -> usize {
+        (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+            .filter(|(dx, dy)| (*dx, *dy) != (0, 0) && self.is_wall(x + dx, y + dy))
+            .count()
+    }
+}
+
+/// A rectangle of tiles inside a chunk, `max` is exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Room {
+    pub min: UVec2,
+    pub max: UVec2,
+}
+
+impl Room {
+    pub fn size(&self) -> UVec2 {
+        self.max - self.min
+    }
+
+    pub fn center(&self) -> UVec2 {
+        (self.min + self.max) / 2
+    }
+
+    /// Whether the rooms overlap or touch each other
+    fn touches(&self, other: &Room) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+    }
+
+    /// A random room of `min_size` to `max_size` tiles that fits into this one
+    fn random_room_inside(&self, rng: &mut StdRng, min_size: u32, max_size: u32) -> Room {
+        let max_size = max_size.min(self.size().x).min(self.size().y).max(min_size);
+        let size = UVec2::new(
+            rng.random_range(min_size..=max_size).min(self.size().x),
+            rng.random_range(min_size..=max_size).min(self.size().y),
+        );
+        let min = UVec2::new(
+            rng.random_range(self.min.x..=self.max.x - size.x),
+            rng.random_range(self.min.y..=self.max.y - size.y),
+        );
+        Room {
+            min,
+            max: min + size,
+        }
+    }
+}
+
+/// The center of the chunk, a tile every generator keeps walkable if it has nothing better
+fn chunk_center() -> UVec2 {
+    CHUNK_SIZE / 2
+}
+
+/// The whole chunk without its outer ring of tiles
+fn chunk_interior() -> Room {
+    Room {
+        min: UVec2::ONE,
+        max: CHUNK_SIZE - UVec2::ONE,
+    }
+}
+
+/// Connect every gate to the closest of the given tiles
+fn connect_gates(grid: &mut WallGrid, gates: &ChunkGates, targets: &[UVec2]) {
+    let closest = |gate: UVec2| {
+        targets
+            .iter()
+            .min_by_key(|target| gate.as_ivec2().distance_squared(target.as_ivec2()))
+            .copied()
+            .unwrap_or_else(chunk_center)
+    };
+
+    // the corridors leave the gates straight into the chunk instead of along its edge
+    for gate in [gates.east, gates.west] {
+        grid.carve_corridor(gate, closest(gate));
+    }
+    for gate in [gates.north, gates.south] {
+        grid.carve_corridor(closest(gate), gate);
+    }
+}
+
+/// Scattered walls, connected through straight corridors from each gate to the center
+pub struct NoiseGenerator;
+
+impl MapGenerator for NoiseGenerator {
+    fn generate(&self, rng: &mut StdRng, gates: &ChunkGates) -> WallGrid {
+        const WALL_CHANCE: f32 = 0.2;
+        let mut grid = WallGrid::open();
+
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                grid.set_wall(x, y, rng.random::<f32>() < WALL_CHANCE);
+            }
+        }
+
+        connect_gates(&mut grid, gates, &[chunk_center()]);
+        grid
+    }
+}
+
+/// Rectangular rooms connected one after another by corridors
+pub struct RoomsAndCorridors;
+
+impl MapGenerator for RoomsAndCorridors {
+    fn generate(&self, rng: &mut StdRng, gates: &ChunkGates) -> WallGrid {
+        const MAX_ROOMS: usize = 6;
+        const PLACEMENT_ATTEMPTS: usize = 40;
+        const MIN_ROOM_SIZE: u32 = 4;
+        const MAX_ROOM_SIZE: u32 = 9;
+        let mut grid = WallGrid::filled();
+        let mut rooms: Vec<Room> = Vec::new();
+
+        for _ in 0..PLACEMENT_ATTEMPTS {
+            if rooms.len() >= MAX_ROOMS {
+                break;
+            }
+
+            let room = chunk_interior().random_room_inside(rng, MIN_ROOM_SIZE, MAX_ROOM_SIZE);
+            if rooms.iter().any(|other| other.touches(&room)) {
+                continue;
+            }
+
+            grid.carve_room(&room);
+            if let Some(previous) = rooms.last() {
+                grid.carve_corridor(previous.center(), room.center());
+            }
+            rooms.push(room);
+        }
+
+        let centers: Vec<UVec2> = rooms.iter().map(Room::center).collect();
+        connect_gates(&mut grid, gates, &centers);
+        grid
+    }
+}
+
+/// Organic caves grown with a cellular automaton from random noise
+pub struct Caves;
+
+impl MapGenerator for Caves {
+    fn generate(&self, rng: &mut StdRng, gates: &ChunkGates) -> WallGrid {
+        const INITIAL_WALL_CHANCE: f32 = 0.45;
+        const SMOOTHING_STEPS: usize = 4;
+        // a tile becomes a wall with at least this many walls around it
+        const WALL_THRESHOLD: usize = 5;
+        let mut grid = WallGrid::filled();
+
+        for y in 1..HEIGHT - 1 {
+            for x in 1..WIDTH - 1 {
+                grid.set_wall(x, y, rng.random::<f32>() < INITIAL_WALL_CHANCE);
+            }
+        }
+
+        for _ in 0..SMOOTHING_STEPS {
+            let mut next = grid.clone();
+            for y in 1..HEIGHT - 1 {
+                for x in 1..WIDTH - 1 {
+                    next.set_wall(x, y, grid.walls_around(x, y) >= WALL_THRESHOLD);
+                }
+            }
+            grid = next;
+        }
+
+        // a small open space in the middle the gates lead to
+        let center = chunk_center();
+        grid.carve_room(&Room {
+            min: center - UVec2::splat(1),
+            max: center + UVec2::splat(2),
+        });
+        connect_gates(&mut grid, gates, &[center]);
+        grid
+    }
+}
+
+/// Binary space partitioning: the chunk is split in two again and again, every part gets a
+/// room and the rooms of both halves of a split are connected
+pub struct Bsp;
+
+impl Bsp {
+    const MIN_LEAF_SIZE: u32 = 8;
+    const MAX_DEPTH: u32 = 4;
+
+    /// Split `leaf` until it is too small, carving the rooms of its parts.
+    /// Returns the centers of all rooms inside it.
+    fn split(rng: &mut StdRng, grid: &mut WallGrid, leaf: Room, depth: u32) -> Vec<UVec2> {
+        let size = leaf.size();
+        let split_vertically = size.x >= size.y;
+        let length = if split_vertically { size.x } else { size.y };
+
+        if depth >= Self::MAX_DEPTH || length < Self::MIN_LEAF_SIZE * 2 {
+            // leave a tile of wall between the room and the leaf's border
+            let inner = Room {
+                min: leaf.min + UVec2::ONE,
+                max: leaf.max - UVec2::ONE,
+            };
+            let room = inner.random_room_inside(rng, 3, 8);
+            grid.carve_room(&room);
+            return vec![room.center()];
+        }
+
+        let at = rng.random_range(Self::MIN_LEAF_SIZE..=length - Self::MIN_LEAF_SIZE);
+        let (first, second) = if split_vertically {
+            (
+                Room {
+                    min: leaf.min,
+                    max: UVec2::new(leaf.min.x + at, leaf.max.y),
+                },
+                Room {
+                    min: UVec2::new(leaf.min.x + at, leaf.min.y),
+                    max: leaf.max,
+                },
+            )
+        } else {
+            (
+                Room {
+                    min: leaf.min,
+                    max: UVec2::new(leaf.max.x, leaf.min.y + at),
+                },
+                Room {
+                    min: UVec2::new(leaf.min.x, leaf.min.y + at),
+                    max: leaf.max,
+                },
+            )
+        };
+
+        let mut centers = Self::split(rng, grid, first, depth + 1);
+        let second_centers = Self::split(rng, grid, second, depth + 1);
+        grid.carve_corridor(centers[0], second_centers[0]);
+        centers.extend(second_centers);
+        centers
+    }
+}
+
+impl MapGenerator for Bsp {
+    fn generate(&self, rng: &mut StdRng, gates: &ChunkGates) -> WallGrid {
+        let mut grid = WallGrid::filled();
+        let centers = Self::split(
+            rng,
+            &mut grid,
+            Room {
+                min: UVec2::ZERO,
+                max: CHUNK_SIZE,
+            },
+            0,
+        );
+        connect_gates(&mut grid, gates, &centers);
+        grid
+    }
+}
+
+/// Groups the walkable tiles of the loaded chunks into regions that can't reach `from` or each
+/// other with orthogonal steps.
+/// Every chunk is connected to its neighbours through its gates, so this should always be
+/// empty. Used to validate the generators.
+pub fn isolated_regions(
+    world_grid: &WorldGrid,
+    tiles: &TileRegistry,
+    from: GridPos,
+) -> Vec<Vec<GridPos>> {
+    let walkable = |pos: &GridPos| GameGrid::is_walkable(pos, world_grid, tiles);
+    let mut reached = HashSet::new();
+    let flood_fill = |start: GridPos, reached: &mut HashSet<GridPos>| {
+        let mut region = vec![start];
+        let mut queue = VecDeque::from([start]);
+        reached.insert(start);
+
+        while let Some(pos) = queue.pop_front() {
+            for direction in [IVec2::Y, IVec2::X, IVec2::NEG_Y, IVec2::NEG_X] {
+                let next = pos.offset(direction);
+                if walkable(&next) && reached.insert(next) {
+                    region.push(next);
+                    queue.push_back(next);
+                }
+            }
+        }
+        region
+    };
+
+    if walkable(&from) {
+        flood_fill(from, &mut reached);
+    }
+
+    let mut regions = Vec::new();
+    for (pos, _) in world_grid.iter() {
+        if walkable(pos) && !reached.contains(pos) {
+            regions.push(flood_fill(*pos, &mut reached));
+        }
+    }
+    regions
+}
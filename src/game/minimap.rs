@@ -0,0 +1,446 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    components::{FieldOfView, Player},
+    states::Screen,
+};
+
+use bevy_ecs_tilemap::tiles::TilePos;
+
+use super::{
+    chunk_store::{ChunkManager, StoredChunk},
+    devil::Devil,
+    fog_of_war::{FogOfWar, FogRevealed},
+    input::{Action, ActionState},
+    map::{CHUNK_SIZE, GameGrid, GridMovement, GridPos},
+    save::GameLoaded,
+    tiles::{TileKind, TileRegistry},
+    world_grid::WorldGrid,
+};
+
+/// Tiles shown along each side of the minimap, one pixel per tile
+const MINIMAP_TILES: u32 = 192;
+/// The map is recentered on the player once it gets this close to its edge
+const RECENTER_MARGIN: i32 = 32;
+const MINIMAP_WIDGET_SIZE: f32 = 192.0;
+const MARKER_SIZE: f32 = 4.0;
+const UNEXPLORED_COLOR: Color = Color::srgba(0.0, 0.0, 0.0, 0.0);
+const PLAYER_MARKER_COLOR: Color = Color::srgb(1.0, 1.0, 0.3);
+const DEVIL_MARKER_COLOR: Color = Color::srgb(1.0, 0.1, 0.1);
+const CHUNK_BORDER_COLOR: Color = Color::srgba(0.4, 0.7, 1.0, 0.4);
+
+/// An overview of everything the player explored around it, drawn into an image.
+/// Tiles are only drawn once they are revealed, the whole image is only redrawn when the map
+/// is recentered. Markers for the player, visible devils and loaded chunks are UI nodes on top.
+#[derive(Resource, Debug)]
+pub struct Minimap {
+    pub image: Handle<Image>,
+    /// grid position of the bottom left pixel
+    origin: GridPos,
+    /// kind of every explored tile, remembered after its chunk despawned
+    explored: HashMap<GridPos, TileKind>,
+    /// revealed positions whose tiles are not spawned yet.
+    /// Only kept while their chunk is spawned.
+    pending: HashSet<GridPos>,
+    /// shown big in the middle of the screen instead of in the corner
+    pub full_screen: bool,
+}
+
+impl Minimap {
+    /// Pixel of the image that shows the position, if the position is on the map
+    fn pixel(&self, pos: &GridPos) -> Option<(u32, u32)> {
+        let x = pos.x - self.origin.x;
+        let y = pos.y - self.origin.y;
+        let size = MINIMAP_TILES as i32;
+        if !(0..size).contains(&x) || !(0..size).contains(&y) {
+            return None;
+        }
+
+        // image rows go from top to bottom, grid positions from south to north
+        Some((x as u32, (size - 1 - y) as u32))
+    }
+
+    /// Where the position is on the map, from the bottom left corner, between 0 and 1
+    fn relative_position(&self, pos: &GridPos) -> Vec2 {
+        Vec2::new(
+            (pos.x - self.origin.x) as f32,
+            (pos.y - self.origin.y) as f32,
+        ) / MINIMAP_TILES as f32
+    }
+}
+
+#[derive(Component)]
+struct MinimapWidget;
+
+/// Outline of a spawned chunk on top of the map image, moved whenever the chunks change
+#[derive(Component)]
+struct ChunkMarker;
+
+/// Dot for the player or a visible devil on top of the map image, moved whenever they move
+#[derive(Component)]
+struct ActorMarker;
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(Startup, create_minimap)
+        .add_systems(OnEnter(Screen::Gameplay), spawn_minimap)
+        .add_systems(OnExit(Screen::Gameplay), forget_explored_tiles)
+        .add_systems(
+            Update,
+            (
+                reveal_after_load,
+                queue_revealed_tiles,
+                recenter_minimap,
+                draw_revealed_tiles.run_if(resource_exists::<TileRegistry>),
+                update_markers,
+                toggle_full_screen_map,
+            )
+                .chain()
+                .run_if(in_state(Screen::Gameplay)),
+        );
+}
+
+fn create_minimap(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let image = Image::new_fill(
+        Extent3d {
+            width: MINIMAP_TILES,
+            height: MINIMAP_TILES,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+
+    commands.insert_resource(Minimap {
+        image: images.add(image),
+        origin: GridPos { x: 0, y: 0 },
+        explored: HashMap::new(),
+        pending: HashSet::new(),
+        full_screen: false,
+    });
+}
+
+fn spawn_minimap(mut commands: Commands, minimap: Res<Minimap>) {
+    commands
+        .spawn((
+            Name::new("Minimap"),
+            MinimapWidget,
+            minimap_node(minimap.full_screen),
+            BackgroundColor(Color::srgba(0.0, 0.0, 0.0, 0.7)),
+            // lets clicks on the map be told apart from clicks on the world
+            Interaction::default(),
+            StateScoped(Screen::Gameplay),
+        ))
+        .with_child((
+            ImageNode::new(minimap.image.clone()),
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+        ));
+}
+
+fn minimap_node(full_screen: bool) -> Node {
+    if full_screen {
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Vh(5.0),
+            left: Val::Percent(50.0),
+            margin: UiRect::left(Val::Vh(-45.0)),
+            width: Val::Vh(90.0),
+            height: Val::Vh(90.0),
+            overflow: Overflow::clip(),
+            ..default()
+        }
+    } else {
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
+            right: Val::Px(12.0),
+            width: Val::Px(MINIMAP_WIDGET_SIZE),
+            height: Val::Px(MINIMAP_WIDGET_SIZE),
+            overflow: Overflow::clip(),
+            ..default()
+        }
+    }
+}
+
+/// Start over for the next run
+fn forget_explored_tiles(mut minimap: ResMut<Minimap>, mut images: ResMut<Assets<Image>>) {
+    minimap.explored.clear();
+    minimap.pending.clear();
+    if let Some(image) = images.get_mut(&minimap.image) {
+        clear_image(image);
+    }
+}
+
+/// A loaded save replaces everything the player explored.
+/// The remembered tiles are looked up in the spawned and the stored chunks of the save.
+fn reveal_after_load(
+    mut loaded: EventReader<GameLoaded>,
+    fog_of_war: Res<FogOfWar>,
+    world_grid: Res<WorldGrid>,
+    chunk_manager: Res<ChunkManager>,
+    tiles: Option<Res<TileRegistry>>,
+    mut minimap: ResMut<Minimap>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if loaded.read().count() == 0 {
+        return;
+    }
+
+    let stored_chunks: HashMap<IVec2, StoredChunk> =
+        chunk_manager.stored_chunks().into_iter().collect();
+    let stored_kind = |pos: &GridPos| {
+        let chunk_pos = GameGrid::grid_pos_to_chunk_pos(*pos);
+        let chunk = stored_chunks.get(&chunk_pos)?;
+        let corner = GameGrid::tile_pos_to_grid_pos(chunk_pos, TilePos { x: 0, y: 0 });
+        Some(chunk.tile((pos.x - corner.x) as u32, (pos.y - corner.y) as u32))
+    };
+
+    minimap.explored.clear();
+    minimap.pending.clear();
+    for pos in fog_of_war.viewed_positions() {
+        match world_grid.kind(pos).or_else(|| stored_kind(pos)) {
+            Some(kind) => {
+                minimap.explored.insert(*pos, kind);
+            }
+            None => {
+                minimap.pending.insert(*pos);
+            }
+        }
+    }
+
+    if let (Some(tiles), Some(image)) = (tiles, images.get_mut(&minimap.image)) {
+        redraw(image, &minimap, &tiles);
+    }
+}
+
+fn queue_revealed_tiles(mut revealed: EventReader<FogRevealed>, mut minimap: ResMut<Minimap>) {
+    for FogRevealed(positions) in revealed.read() {
+        minimap.pending.extend(positions.iter().copied());
+    }
+}
+
+/// Keep the player away from the edge of the map, redrawing it around the player if needed
+fn recenter_minimap(
+    player_query: Query<&GridMovement, With<Player>>,
+    tiles: Option<Res<TileRegistry>>,
+    mut minimap: ResMut<Minimap>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let Ok(grid_movement) = player_query.get_single() else {
+        return;
+    };
+
+    let player_pos = grid_movement.current_pos;
+    let size = MINIMAP_TILES as i32;
+    let x = player_pos.x - minimap.origin.x;
+    let y = player_pos.y - minimap.origin.y;
+    let near_edge = x < RECENTER_MARGIN
+        || y < RECENTER_MARGIN
+        || x >= size - RECENTER_MARGIN
+        || y >= size - RECENTER_MARGIN;
+    if !near_edge {
+        return;
+    }
+
+    minimap.origin = GridPos {
+        x: player_pos.x - size / 2,
+        y: player_pos.y - size / 2,
+    };
+
+    if let (Some(tiles), Some(image)) = (tiles, images.get_mut(&minimap.image)) {
+        redraw(image, &minimap, &tiles);
+    }
+}
+
+/// Draw the pending tiles that are spawned by now.
+/// Positions in chunks that are not spawned are dropped, their tiles never show up.
+fn draw_revealed_tiles(
+    mut minimap: ResMut<Minimap>,
+    mut images: ResMut<Assets<Image>>,
+    world_grid: Res<WorldGrid>,
+    chunk_manager: Res<ChunkManager>,
+    tiles: Res<TileRegistry>,
+) {
+    if minimap.pending.is_empty() {
+        return;
+    }
+    let Some(image) = images.get_mut(&minimap.image) else {
+        return;
+    };
+
+    let revealed: Vec<(GridPos, TileKind)> = minimap
+        .pending
+        .iter()
+        .filter_map(|pos| Some((*pos, world_grid.kind(pos)?)))
+        .collect();
+
+    for (pos, kind) in revealed {
+        minimap.pending.remove(&pos);
+        minimap.explored.insert(pos, kind);
+        if let Some((x, y)) = minimap.pixel(&pos) {
+            let _ = image.set_color_at(x, y, tile_color(&tiles, kind));
+        }
+    }
+
+    // e.g. revealed right before their chunk despawned
+    minimap.pending.retain(|pos| {
+        chunk_manager
+            .spawned_chunks
+            .contains(&GameGrid::grid_pos_to_chunk_pos(*pos))
+    });
+}
+
+fn tile_color(tiles: &TileRegistry, kind: TileKind) -> Color {
+    let (r, g, b) = tiles.get(kind).map_color;
+    Color::srgb_u8(r, g, b)
+}
+
+/// Draw every explored tile again, e.g. after the map was moved
+fn redraw(image: &mut Image, minimap: &Minimap, tiles: &TileRegistry) {
+    clear_image(image);
+    for (pos, kind) in minimap.explored.iter() {
+        if let Some((x, y)) = minimap.pixel(pos) {
+            let _ = image.set_color_at(x, y, tile_color(tiles, *kind));
+        }
+    }
+}
+
+fn clear_image(image: &mut Image) {
+    for y in 0..MINIMAP_TILES {
+        for x in 0..MINIMAP_TILES {
+            let _ = image.set_color_at(x, y, UNEXPLORED_COLOR);
+        }
+    }
+}
+
+fn update_markers(
+    mut commands: Commands,
+    minimap: Res<Minimap>,
+    chunk_manager: Res<ChunkManager>,
+    widget_query: Query<Entity, With<MinimapWidget>>,
+    mut chunk_marker_query: Query<(Entity, &mut Node), (With<ChunkMarker>, Without<ActorMarker>)>,
+    mut actor_marker_query: Query<
+        (Entity, &mut Node, &mut BackgroundColor),
+        (With<ActorMarker>, Without<ChunkMarker>),
+    >,
+    player_query: Query<(&GridMovement, &FieldOfView), With<Player>>,
+    devil_query: Query<&GridMovement, With<Devil>>,
+    moved_query: Query<(), Changed<GridMovement>>,
+) {
+    let Ok(widget) = widget_query.get_single() else {
+        return;
+    };
+    let Ok((player_movement, fov)) = player_query.get_single() else {
+        return;
+    };
+
+    let has_markers = !actor_marker_query.is_empty();
+    if has_markers && !minimap.is_changed() && !chunk_manager.is_changed() && moved_query.is_empty()
+    {
+        return;
+    }
+
+    let mut chunk_nodes = chunk_manager
+        .spawned_chunks
+        .iter()
+        .map(|chunk_pos| chunk_marker_node(&minimap, *chunk_pos));
+
+    let visible_devils = devil_query
+        .iter()
+        .filter(|movement| fov.visible_positions.contains(&movement.current_pos))
+        .map(|movement| (movement.current_pos, DEVIL_MARKER_COLOR));
+    let player = std::iter::once((player_movement.current_pos, PLAYER_MARKER_COLOR));
+    let mut actor_markers = visible_devils
+        .chain(player)
+        .map(|(pos, color)| (actor_marker_node(&minimap, pos), BackgroundColor(color)));
+
+    // move the existing markers, only spawning or despawning the ones that are missing or left over
+    for (marker, mut node) in chunk_marker_query.iter_mut() {
+        match chunk_nodes.next() {
+            Some(new_node) => {
+                node.set_if_neq(new_node);
+            }
+            None => commands.entity(marker).despawn_recursive(),
+        }
+    }
+    for (marker, mut node, mut background) in actor_marker_query.iter_mut() {
+        match actor_markers.next() {
+            Some((new_node, new_background)) => {
+                node.set_if_neq(new_node);
+                background.set_if_neq(new_background);
+            }
+            None => commands.entity(marker).despawn_recursive(),
+        }
+    }
+
+    commands.entity(widget).with_children(|children| {
+        for node in chunk_nodes {
+            children.spawn((ChunkMarker, node, BorderColor(CHUNK_BORDER_COLOR)));
+        }
+        for (node, background) in actor_markers {
+            // drawn above the chunk borders, no matter when they were spawned
+            children.spawn((ActorMarker, node, background, ZIndex(1)));
+        }
+    });
+}
+
+fn chunk_marker_node(minimap: &Minimap, chunk_pos: IVec2) -> Node {
+    let chunk_size = CHUNK_SIZE.as_vec2() / MINIMAP_TILES as f32 * 100.0;
+    let corner = GameGrid::chunk_pos_to_world_pos(chunk_pos).truncate();
+    let position = minimap.relative_position(&GridPos::from_world_pos(corner)) * 100.0;
+    Node {
+        position_type: PositionType::Absolute,
+        left: Val::Percent(position.x),
+        bottom: Val::Percent(position.y),
+        width: Val::Percent(chunk_size.x),
+        height: Val::Percent(chunk_size.y),
+        border: UiRect::all(Val::Px(1.0)),
+        ..default()
+    }
+}
+
+fn actor_marker_node(minimap: &Minimap, pos: GridPos) -> Node {
+    let position = minimap.relative_position(&pos) * 100.0;
+    Node {
+        position_type: PositionType::Absolute,
+        left: Val::Percent(position.x),
+        bottom: Val::Percent(position.y),
+        width: Val::Px(MARKER_SIZE),
+        height: Val::Px(MARKER_SIZE),
+        margin: UiRect::new(
+            Val::Px(-MARKER_SIZE / 2.0),
+            Val::ZERO,
+            Val::ZERO,
+            Val::Px(-MARKER_SIZE / 2.0),
+        ),
+        ..default()
+    }
+}
+
+fn toggle_full_screen_map(
+    action_state: Res<ActionState>,
+    mut minimap: ResMut<Minimap>,
+    mut widget_query: Query<&mut Node, With<MinimapWidget>>,
+) {
+    if !action_state.just_pressed(Action::ToggleMap) {
+        return;
+    }
+
+    minimap.full_screen = !minimap.full_screen;
+    for mut node in widget_query.iter_mut() {
+        *node = minimap_node(minimap.full_screen);
+    }
+}
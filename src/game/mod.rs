@@ -1,23 +1,97 @@
 use bevy::prelude::*;
 
+use crate::states::{Pause, Screen};
+
 pub mod animation;
+pub mod assets;
 mod camera;
+pub mod chunk_store;
+pub mod combat;
+pub mod cursor;
 pub mod devil;
 pub mod fog_of_war;
 pub mod fov;
+pub mod game_log;
+pub mod input;
+pub mod look;
 pub mod map;
+pub mod map_generator;
+pub mod minimap;
+pub mod occupancy;
+pub mod path_cache;
 pub mod player;
+pub mod save;
+pub mod tiles;
 pub mod turns;
+pub mod world_grid;
 
+/// The game with rendering, input and camera on top of the simulation
 pub(super) fn plugin(app: &mut App) {
     app.add_plugins((
+        simulation_plugin,
+        assets::plugin,
+        input::plugin,
+        cursor::plugin,
+        look::plugin,
+        map::presentation_plugin,
+        fov::presentation_plugin,
+        fog_of_war::presentation_plugin,
+        player::presentation_plugin,
+        devil::presentation_plugin,
+        save::presentation_plugin,
+        game_log::presentation_plugin,
+        minimap::plugin,
+        camera::plugin,
+        animation::plugin,
+    ));
+}
+
+/// The game without anything that needs a window or GPU, see SimulationPlugin
+/// Starts right on the Gameplay screen, there are no menus to go through.
+pub(super) fn headless_plugin(app: &mut App) {
+    app.insert_state(Screen::Gameplay)
+        .add_sub_state::<Pause>()
+        .enable_state_scoped_entities::<Screen>();
+    app.add_plugins((simulation_plugin, map::headless_plugin));
+}
+
+/// Game logic shared by the rendered and the headless game:
+/// map generation, turns, fov and ai
+fn simulation_plugin(app: &mut App) {
+    app.add_plugins((
+        tiles::plugin,
         map::plugin,
+        world_grid::plugin,
+        occupancy::plugin,
+        path_cache::plugin,
         fov::plugin,
         fog_of_war::plugin,
         player::plugin,
         devil::plugin,
-        camera::plugin,
-        animation::plugin,
+        combat::plugin,
+        save::plugin,
         turns::plugin,
+        game_log::plugin,
     ));
+    app.add_systems(OnExit(Screen::Gameplay), reset_run);
+}
+
+/// The entities of a run are StateScoped to Screen::Gameplay, this forgets everything else
+/// about it so the next run starts from a fresh world.
+fn reset_run(
+    mut seed: ResMut<map::WorldSeed>,
+    mut world_grid: ResMut<world_grid::WorldGrid>,
+    mut occupancy: ResMut<occupancy::Occupancy>,
+    mut chunk_manager: ResMut<chunk_store::ChunkManager>,
+    mut fog_of_war: ResMut<fog_of_war::FogOfWar>,
+    mut turn_queue: ResMut<turns::TurnQueue>,
+    mut game_log: ResMut<game_log::GameLog>,
+) {
+    *seed = map::WorldSeed::default();
+    world_grid.clear();
+    occupancy.clear();
+    chunk_manager.clear();
+    fog_of_war.set_viewed_positions([]);
+    turn_queue.reset(0);
+    game_log.clear();
 }
@@ -0,0 +1,102 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use super::map::{GridMovement, GridPos};
+
+/// Which entity occupies which grid position.
+/// An entity occupies its current position and, while it is moving, its target position.
+/// Kept in sync with GridMovement, but movement code should `reserve` a target right away, so
+/// other entities moving in the same frame can't pick the same position.
+#[derive(Resource, Default, Debug)]
+pub struct Occupancy {
+    occupants: HashMap<GridPos, Entity>,
+    positions: HashMap<Entity, (GridPos, Option<GridPos>)>,
+    /// increased on every change
+    revision: u64,
+}
+
+impl Occupancy {
+    pub fn occupant(&self, pos: &GridPos) -> Option<Entity> {
+        self.occupants.get(pos).copied()
+    }
+
+    pub fn is_occupied(&self, pos: &GridPos) -> bool {
+        self.occupants.contains_key(pos)
+    }
+
+    /// Whether the position is occupied by anything else than `entity`
+    pub fn is_occupied_by_other(&self, pos: &GridPos, entity: Entity) -> bool {
+        self.occupant(pos)
+            .is_some_and(|occupant| occupant != entity)
+    }
+
+    /// A number that changes whenever an entity is placed or removed.
+    /// Use it to invalidate anything derived from the occupancy, like cached paths.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Place an entity at a position, replacing whatever it occupied before
+    pub fn place(&mut self, entity: Entity, current: GridPos, target: Option<GridPos>) {
+        self.remove(entity);
+        self.occupants.insert(current, entity);
+        if let Some(target) = target {
+            self.occupants.insert(target, entity);
+        }
+        self.positions.insert(entity, (current, target));
+        self.revision += 1;
+    }
+
+    /// Reserve `target` as the position `entity` is moving to.
+    /// Returns false if another entity already occupies it.
+    pub fn reserve(&mut self, entity: Entity, target: GridPos) -> bool {
+        if self.is_occupied_by_other(&target, entity) {
+            return false;
+        }
+
+        let current = self
+            .positions
+            .get(&entity)
+            .map_or(target, |(current, _)| *current);
+        self.place(entity, current, Some(target));
+        true
+    }
+
+    /// Forget every entity, e.g. before loading a save game
+    pub fn clear(&mut self) {
+        self.occupants.clear();
+        self.positions.clear();
+        self.revision += 1;
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        let Some((current, target)) = self.positions.remove(&entity) else {
+            return;
+        };
+
+        for pos in std::iter::once(current).chain(target) {
+            if self.occupants.get(&pos) == Some(&entity) {
+                self.occupants.remove(&pos);
+            }
+        }
+        self.revision += 1;
+    }
+}
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<Occupancy>()
+        .add_systems(PostUpdate, sync_occupancy);
+}
+
+fn sync_occupancy(
+    mut occupancy: ResMut<Occupancy>,
+    moved_query: Query<(Entity, &GridMovement), Changed<GridMovement>>,
+    mut removed: RemovedComponents<GridMovement>,
+) {
+    for entity in removed.read() {
+        occupancy.remove(entity);
+    }
+
+    for (entity, movement) in moved_query.iter() {
+        occupancy.place(entity, movement.current_pos, movement.target_pos);
+    }
+}
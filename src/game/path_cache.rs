@@ -0,0 +1,67 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{
+    map::{GameGrid, GridPos, MovementTopology, PathBudget, PathResult},
+    occupancy::Occupancy,
+    tiles::TileRegistry,
+    world_grid::WorldGrid,
+};
+
+/// Remembers path searches until the world grid or the occupancy changes.
+/// Paths are searched every frame for the hovered tile, while the grid only changes between turns.
+#[derive(Resource, Debug, Default)]
+pub struct PathCache {
+    /// world grid revision, occupancy revision, topology and budget the cached paths were
+    /// searched with
+    revision: (u64, u64, MovementTopology, PathBudget),
+    paths: HashMap<(GridPos, GridPos), Option<PathResult>>,
+}
+
+impl PathCache {
+    /// max amount of cached searches before the cache is cleared
+    const MAX_ENTRIES: usize = 1024;
+
+    /// Find a path from start to goal, see GameGrid::find_grid_path
+    pub fn find_path(
+        &mut self,
+        world_grid: &WorldGrid,
+        tiles: &TileRegistry,
+        occupancy: &Occupancy,
+        topology: MovementTopology,
+        budget: &PathBudget,
+        start: GridPos,
+        goal: GridPos,
+    ) -> Option<PathResult> {
+        let revision = (
+            world_grid.revision(),
+            occupancy.revision(),
+            topology,
+            *budget,
+        );
+        if revision != self.revision || self.paths.len() >= Self::MAX_ENTRIES {
+            self.paths.clear();
+            self.revision = revision;
+        }
+
+        self.paths
+            .entry((start, goal))
+            .or_insert_with(|| {
+                GameGrid::find_grid_path(
+                    world_grid, tiles, occupancy, topology, budget, start, goal,
+                )
+            })
+            .clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+}
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<PathCache>();
+}
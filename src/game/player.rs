@@ -1,140 +1,481 @@
+use std::collections::VecDeque;
+
 use crate::components::{AnimationConfig, FieldOfView, TurnTaker};
-use crate::states::TurnState;
-use bevy::prelude::*;
-use bevy_ecs_tilemap::prelude::*;
+use crate::states::{Pause, Screen, TurnState};
+use bevy::{prelude::*, utils::HashSet};
 
 use super::{
-    camera::FollowedByCamera,
-    map::{GameGrid, GridMovement, GridPos},
+    assets::GameAssets,
+    combat::{Attack, AttackEvent, Dead, Defense, Health},
+    cursor::tile_cursor_inactive,
+    devil::Devil,
+    game_log::{LogKind, LogMessage},
+    input::{Action, ActionState},
+    map::{
+        GameGrid, GridMovement, GridPos, HoveredTilePos, MovementTopology, PathBudget, START_POS,
+    },
+    occupancy::Occupancy,
+    path_cache::PathCache,
+    tiles::TileRegistry,
+    turns::{ACTION_COST, NORMAL_SPEED, TurnQueue, move_cost},
+    world_grid::WorldGrid,
 };
 
 #[derive(Component)]
 pub struct Player;
 
+/// Something the player wants to do on its turn.
+/// Sent by the input systems, or directly when running a SimulationPlugin.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerAction {
+    /// step one tile into the direction
+    Move(IVec2),
+    /// walk to the position over the next turns, one action per step
+    TravelTo(GridPos),
+    /// do nothing for a single action
+    Wait,
+    /// keep waiting until an enemy comes into view, the player gets hurt or `turns` actions passed
+    Rest { turns: u32 },
+}
+
+/// How many actions the rest key rests for, unless something interrupts it
+pub const REST_TURNS: u32 = 20;
+
+/// A multi-turn walk the player is following, e.g. after clicking on a tile.
+/// Removed once the goal is reached or something interrupts it.
+#[derive(Component, Debug)]
+pub struct MovementPlan {
+    /// remaining positions to step on, the next one first
+    pub steps: VecDeque<GridPos>,
+    /// devils that were already visible when the plan was made, they don't interrupt it
+    known_devils: HashSet<Entity>,
+}
+
+/// The player is resting, waiting one action at a time.
+/// Removed once the turns are over or something interrupts it.
+#[derive(Component, Debug)]
+pub struct Resting {
+    pub turns_left: u32,
+    /// health when resting started, losing any interrupts the rest
+    health: u32,
+}
+
 pub(super) fn plugin(app: &mut App) {
-    app.add_systems(Startup, spawn)
-        .add_systems(Update, take_turn.run_if(in_state(TurnState::Player)));
+    app.add_event::<PlayerAction>()
+        .add_systems(OnEnter(Screen::Gameplay), spawn)
+        .add_systems(
+            Update,
+            (take_turn, follow_movement_plan, rest)
+                .chain()
+                .run_if(in_state(Pause::Running))
+                .run_if(in_state(TurnState::Player))
+                .run_if(resource_exists::<TileRegistry>),
+        );
 }
 
-fn spawn(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
-) {
-    // The only thing we have in our level is a player,
-    // but add things like walls etc. here.
-    // TODO:
-    let player_asset = asset_server.load("images/player/idle/idle.png");
-    let layout = TextureAtlasLayout::from_grid(UVec2::new(48, 64), 8, 1, None, None);
-    let texture_atlas_layout = texture_atlas_layouts.add(layout);
-    let animation_config = AnimationConfig::new(0, 7, 10, true);
+pub(super) fn presentation_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            (
+                read_input_actions.run_if(tile_cursor_inactive),
+                read_mouse_input,
+            )
+                .run_if(in_state(Pause::Running)),
+            add_sprite,
+            play_death_animation,
+            end_game_after_death,
+        ),
+    );
+}
 
+fn spawn(mut commands: Commands) {
     commands.spawn((
         Name::new("Player"),
         Player, // TODO: add the rest as required components?
-        Transform {
-            translation: Vec3 {
-                x: 0.0,
-                y: 0.0,
-                z: 1.,
-            },
-            ..default()
-        },
+        // map generation keeps the start walkable and connected to the rest of the world
+        Transform::from_translation(START_POS.to_world_pos().extend(1.)),
         GridMovement {
-            current_pos: GridPos { x: 0, y: 0 },
+            current_pos: START_POS,
             target_pos: None,
         },
-        TurnTaker {
-            actions_per_turn: 2, // -- movement takes an action!
-            actions_remaining: 2,
-        },
+        // acts twice for every devil action
+        TurnTaker::new(NORMAL_SPEED * 2),
         FieldOfView::new(10),
-        Sprite {
-            image: player_asset,
-            texture_atlas: Some(TextureAtlas {
-                layout: texture_atlas_layout.clone(),
-                index: animation_config.first_sprite_index,
-            }),
-
-            ..default()
-        },
-        animation_config,
+        Health::new(20),
+        Attack(3),
+        Defense(1),
+        StateScoped(Screen::Gameplay),
     ));
 }
 
 fn take_turn(
-    mut movement_query: Query<(Entity, &mut TurnTaker, &mut GridMovement, Option<&Player>)>,
-    chunks_query: Query<(
-        &TileStorage,
-        &TilemapSize,
-        &TilemapGridSize,
-        &TilemapType,
-        &Transform,
-    )>,
-    tile_query: Query<&TileTextureIndex>,
     mut commands: Commands,
-    key: Res<ButtonInput<KeyCode>>,
+    mut player_query: Query<
+        (
+            Entity,
+            &mut TurnTaker,
+            &mut GridMovement,
+            &FieldOfView,
+            &Health,
+        ),
+        (With<Player>, Without<Dead>),
+    >,
+    devil_query: Query<(Entity, &GridMovement), (With<Devil>, Without<Player>)>,
+    attackable_query: Query<(), With<Health>>,
+    world_grid: Res<WorldGrid>,
+    tiles: Res<TileRegistry>,
+    mut occupancy: ResMut<Occupancy>,
+    topology: Res<MovementTopology>,
+    budget: Res<PathBudget>,
+    mut path_cache: ResMut<PathCache>,
+    turn_queue: Res<TurnQueue>,
+    mut actions: EventReader<PlayerAction>,
+    mut attacks: EventWriter<AttackEvent>,
+    mut log: EventWriter<LogMessage>,
 ) {
-    let mut occupied_positions: Vec<_> = movement_query
-        .iter()
-        .map(|(entity, _, grid_movement, _)| {
-            (entity, grid_movement.current_pos, grid_movement.target_pos)
-        })
-        .collect();
-
-    let players_to_move: Vec<_> = movement_query
-        .iter_mut()
-        .filter(|(_, _, grid_movement, player)| {
-            player.is_some() && grid_movement.target_pos.is_none()
-        })
-        .collect();
-
-    let mut direction = None;
-
-    if key.just_pressed(KeyCode::KeyW) {
-        direction = Some((0, 1));
-    } else if key.just_pressed(KeyCode::KeyS) {
-        direction = Some((0, -1));
-    } else if key.just_pressed(KeyCode::KeyA) {
-        direction = Some((-1, 0));
-    } else if key.just_pressed(KeyCode::KeyD) {
-        direction = Some((1, 0));
-    }
+    for action in actions.read() {
+        for (entity, mut turn_taker, mut grid_movement, fov, health) in player_query.iter_mut() {
+            // anything the player does by hand takes over from planned walks and resting
+            commands
+                .entity(entity)
+                .remove::<MovementPlan>()
+                .remove::<Resting>();
 
-    players_to_move
-        .into_iter()
-        .for_each(|(entity, mut turn_taker, mut grid_movement, _)| {
-            if let Some((dx, dy)) = direction {
-                let new_pos = GridPos {
-                    x: grid_movement.current_pos.x + dx,
-                    y: grid_movement.current_pos.y + dy,
-                };
-
-                let is_occupied =
-                    occupied_positions
-                        .iter()
-                        .any(|(other_entity, other_current, other_target)| {
-                            if *other_entity == entity {
-                                return false;
+            match *action {
+                PlayerAction::Move(direction) => {
+                    if grid_movement.target_pos.is_some() || !turn_queue.can_act(&turn_taker) {
+                        continue;
+                    }
+
+                    let new_pos = grid_movement.current_pos.offset(direction);
+                    if !GameGrid::can_step(
+                        &grid_movement.current_pos,
+                        &new_pos,
+                        *topology,
+                        &world_grid,
+                        &tiles,
+                    ) {
+                        let text = match world_grid.kind(&new_pos) {
+                            Some(kind) if !tiles.get(kind).walkable => {
+                                format!(
+                                    "A {} blocks your way.",
+                                    tiles.get(kind).name.to_lowercase()
+                                )
                             }
-                            *other_current == new_pos
-                                || other_target.is_some_and(|pos| pos == new_pos)
-                        });
+                            _ => "You can't go there.".to_string(),
+                        };
+                        log.send(LogMessage::new(LogKind::Blocked, text));
+                        continue;
+                    }
 
-                if !is_occupied && GameGrid::is_walkable(&new_pos, &chunks_query, &tile_query) {
-                    // update our entities old occupied_position so other entities can't move to
-                    // its new position
-                    if let Some((_, _, target_pos)) = occupied_positions
-                        .iter_mut()
-                        .find(|(other_entity, _, _)| *other_entity == entity)
+                    // bumping into something that can be hurt attacks it
+                    if let Some(target) = occupancy.occupant(&new_pos)
+                        && target != entity
+                        && attackable_query.contains(target)
                     {
-                        *target_pos = Some(new_pos);
+                        attacks.send(AttackEvent {
+                            attacker: entity,
+                            target,
+                        });
+                        turn_taker.spend(ACTION_COST);
+                    } else if occupancy.reserve(entity, new_pos) {
+                        grid_movement.target_pos = Some(new_pos);
+                        turn_taker.spend(move_cost(&new_pos, &world_grid, &tiles));
+                        log.send(LogMessage::new(
+                            LogKind::Info,
+                            format!("You move {}.", direction_name(direction)),
+                        ));
+                    } else {
+                        log.send(LogMessage::new(
+                            LogKind::Blocked,
+                            "Something is in your way.",
+                        ));
+                    }
+                }
+                PlayerAction::TravelTo(goal) => {
+                    let start = grid_movement
+                        .target_pos
+                        .unwrap_or(grid_movement.current_pos);
+                    // without a complete path, walk as close to the goal as possible
+                    let Some(result) = path_cache.find_path(
+                        &world_grid,
+                        &tiles,
+                        &occupancy,
+                        *topology,
+                        &budget,
+                        start,
+                        goal,
+                    ) else {
+                        continue;
+                    };
+
+                    let mut steps = VecDeque::from(result.path);
+                    // the path starts at our own position
+                    steps.pop_front();
+                    if steps.is_empty() {
+                        continue;
                     }
 
-                    grid_movement.target_pos = Some(new_pos);
-                    turn_taker.actions_remaining -= 1;
-                    commands.entity(entity).insert(FollowedByCamera);
+                    let known_devils = devil_query
+                        .iter()
+                        .filter(|(_, devil_movement)| {
+                            fov.visible_positions.contains(&devil_movement.current_pos)
+                        })
+                        .map(|(devil, _)| devil)
+                        .collect();
+
+                    commands.entity(entity).insert(MovementPlan {
+                        steps,
+                        known_devils,
+                    });
+                }
+                PlayerAction::Wait => {
+                    if grid_movement.target_pos.is_some() || !turn_queue.can_act(&turn_taker) {
+                        continue;
+                    }
+
+                    turn_taker.spend(ACTION_COST);
+                    log.send(LogMessage::new(LogKind::Info, "You wait."));
+                }
+                PlayerAction::Rest { turns } => {
+                    commands.entity(entity).insert(Resting {
+                        turns_left: turns,
+                        health: health.current,
+                    });
+                    log.send(LogMessage::new(LogKind::Info, "You rest."));
                 }
             }
+        }
+    }
+}
+
+fn direction_name(direction: IVec2) -> &'static str {
+    match (direction.x.signum(), direction.y.signum()) {
+        (0, 1) => "north",
+        (0, -1) => "south",
+        (1, 0) => "east",
+        (-1, 0) => "west",
+        (1, 1) => "north-east",
+        (-1, 1) => "north-west",
+        (1, -1) => "south-east",
+        (-1, -1) => "south-west",
+        _ => "nowhere",
+    }
+}
+
+/// Take one step of the movement plan per action.
+/// Stops when a new devil comes into view or the next step is blocked.
+fn follow_movement_plan(
+    mut commands: Commands,
+    mut player_query: Query<
+        (
+            Entity,
+            &mut TurnTaker,
+            &mut GridMovement,
+            &mut MovementPlan,
+            &FieldOfView,
+        ),
+        (With<Player>, Without<Dead>),
+    >,
+    devil_query: Query<(Entity, &GridMovement), (With<Devil>, Without<Player>)>,
+    world_grid: Res<WorldGrid>,
+    tiles: Res<TileRegistry>,
+    mut occupancy: ResMut<Occupancy>,
+    topology: Res<MovementTopology>,
+    turn_queue: Res<TurnQueue>,
+    mut log: EventWriter<LogMessage>,
+) {
+    for (entity, mut turn_taker, mut grid_movement, mut plan, fov) in player_query.iter_mut() {
+        if grid_movement.target_pos.is_some() || !turn_queue.can_act(&turn_taker) {
+            continue;
+        }
+
+        let spotted_devil = devil_query.iter().any(|(devil, devil_movement)| {
+            !plan.known_devils.contains(&devil)
+                && fov.visible_positions.contains(&devil_movement.current_pos)
         });
+        if spotted_devil {
+            log.send(LogMessage::new(LogKind::Danger, "You stop walking."));
+            commands.entity(entity).remove::<MovementPlan>();
+            continue;
+        }
+
+        let Some(next_pos) = plan.steps.pop_front() else {
+            commands.entity(entity).remove::<MovementPlan>();
+            continue;
+        };
+
+        if !GameGrid::can_step(
+            &grid_movement.current_pos,
+            &next_pos,
+            *topology,
+            &world_grid,
+            &tiles,
+        ) || !occupancy.reserve(entity, next_pos)
+        {
+            log.send(LogMessage::new(
+                LogKind::Blocked,
+                "Your path is blocked, you stop.",
+            ));
+            commands.entity(entity).remove::<MovementPlan>();
+            continue;
+        }
+
+        grid_movement.target_pos = Some(next_pos);
+        turn_taker.spend(move_cost(&next_pos, &world_grid, &tiles));
+
+        if plan.steps.is_empty() {
+            commands.entity(entity).remove::<MovementPlan>();
+        }
+    }
+}
+
+/// Wait one action at a time while resting.
+/// Stops when a devil is in view, the player got hurt or the turns are over.
+fn rest(
+    mut commands: Commands,
+    mut player_query: Query<
+        (
+            Entity,
+            &mut TurnTaker,
+            &GridMovement,
+            &mut Resting,
+            &FieldOfView,
+            &Health,
+        ),
+        (With<Player>, Without<Dead>),
+    >,
+    devil_query: Query<&GridMovement, (With<Devil>, Without<Player>)>,
+    turn_queue: Res<TurnQueue>,
+    mut log: EventWriter<LogMessage>,
+) {
+    for (entity, mut turn_taker, grid_movement, mut resting, fov, health) in player_query.iter_mut()
+    {
+        if grid_movement.target_pos.is_some() || !turn_queue.can_act(&turn_taker) {
+            continue;
+        }
+
+        let devil_in_view = devil_query
+            .iter()
+            .any(|devil_movement| fov.visible_positions.contains(&devil_movement.current_pos));
+        if devil_in_view {
+            log.send(LogMessage::new(
+                LogKind::Danger,
+                "You can't rest with a devil in view.",
+            ));
+            commands.entity(entity).remove::<Resting>();
+            continue;
+        }
+
+        if health.current < resting.health {
+            log.send(LogMessage::new(
+                LogKind::Danger,
+                "You got hurt and stop resting.",
+            ));
+            commands.entity(entity).remove::<Resting>();
+            continue;
+        }
+
+        if resting.turns_left == 0 {
+            commands.entity(entity).remove::<Resting>();
+            continue;
+        }
+
+        resting.turns_left -= 1;
+        turn_taker.spend(ACTION_COST);
+    }
+}
+
+fn add_sprite(
+    mut commands: Commands,
+    player_query: Query<Entity, Added<Player>>,
+    assets: Res<GameAssets>,
+) {
+    for entity in player_query.iter() {
+        let animation_config = AnimationConfig::new(0, 7, 10, true);
+
+        commands.entity(entity).insert((
+            Sprite {
+                image: assets.player_idle.clone(),
+                texture_atlas: Some(TextureAtlas {
+                    layout: assets.player_layout.clone(),
+                    index: animation_config.first_sprite_index,
+                }),
+
+                ..default()
+            },
+            animation_config,
+        ));
+    }
+}
+
+fn play_death_animation(
+    mut commands: Commands,
+    player_query: Query<Entity, (With<Player>, Added<Dead>)>,
+    assets: Res<GameAssets>,
+) {
+    for entity in player_query.iter() {
+        let animation_config = AnimationConfig::new(0, 7, 10, false);
+
+        commands.entity(entity).insert((
+            Sprite {
+                image: assets.player_death.clone(),
+                texture_atlas: Some(TextureAtlas {
+                    layout: assets.player_layout.clone(),
+                    index: animation_config.first_sprite_index,
+                }),
+                ..default()
+            },
+            animation_config,
+        ));
+    }
+}
+
+fn end_game_after_death(
+    player_query: Query<(&Sprite, &AnimationConfig), (With<Player>, With<Dead>)>,
+    mut next_screen: ResMut<NextState<Screen>>,
+) {
+    for (sprite, animation_config) in player_query.iter() {
+        if animation_config.is_finished(sprite) {
+            next_screen.set(Screen::GameOver);
+        }
+    }
+}
+
+fn read_input_actions(action_state: Res<ActionState>, mut actions: EventWriter<PlayerAction>) {
+    if let Some(direction) = Action::MOVEMENT
+        .iter()
+        .filter(|action| action_state.triggered(**action))
+        .find_map(Action::direction)
+    {
+        actions.send(PlayerAction::Move(direction));
+    } else if action_state.just_pressed(Action::Wait) {
+        actions.send(PlayerAction::Wait);
+    } else if action_state.just_pressed(Action::Rest) {
+        actions.send(PlayerAction::Rest { turns: REST_TURNS });
+    }
+}
+
+fn read_mouse_input(
+    mouse: Res<ButtonInput<MouseButton>>,
+    hovered_tile_pos: Res<HoveredTilePos>,
+    ui_query: Query<&Interaction>,
+    mut actions: EventWriter<PlayerAction>,
+) {
+    if !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+
+    // clicks on the UI, e.g. the message log or the minimap, are not meant for the world
+    if ui_query
+        .iter()
+        .any(|interaction| *interaction != Interaction::None)
+    {
+        return;
+    }
+
+    if let Some(pos) = hovered_tile_pos.0 {
+        actions.send(PlayerAction::TravelTo(GridPos::from_world_pos(pos)));
+    }
 }
@@ -0,0 +1,441 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::TileStorage;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{components::TurnTaker, states::Pause};
+
+use super::{
+    chunk_store::{ChunkManager, StoredActor, StoredChunk},
+    combat::{Dead, Health, PlayerDied},
+    devil::Devil,
+    fog_of_war::FogOfWar,
+    game_log::{LogKind, LogMessage},
+    input::{Action, ActionState},
+    map::{ChunkLayout, GameRng, GridMovement, GridPos, WorldSeed, collect_chunk},
+    map_generator::MapGeneratorKind,
+    occupancy::Occupancy,
+    player::{MovementPlan, Player, Resting},
+    tiles::{TileKind, TileRegistry},
+    turns::TurnQueue,
+    world_grid::WorldGrid,
+};
+
+/// Increased whenever the save file format or chunk generation changes, older saves can't be
+/// loaded anymore
+pub const SAVE_VERSION: u32 = 5;
+
+/// Where the quicksave is written to
+#[derive(Resource, Debug, Clone)]
+pub struct SavePath(pub PathBuf);
+
+impl Default for SavePath {
+    fn default() -> Self {
+        Self(PathBuf::from("saves/quicksave.ron"))
+    }
+}
+
+/// Whether dying deletes the save, so a run can't be continued after the player died.
+/// Only the rendered game turns it on, headless runs keep their saves.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Permadeath(pub bool);
+
+/// Write the current game to the SavePath
+#[derive(Event, Debug, Clone, Copy)]
+pub struct SaveGame;
+
+/// Replace the current game with the one at the SavePath
+#[derive(Event, Debug, Clone, Copy)]
+pub struct LoadGame;
+
+/// Sent after a save game was loaded
+#[derive(Event, Debug, Clone, Copy)]
+pub struct GameLoaded;
+
+/// Everything needed to continue a run.
+/// Chunks are regenerated from the seed, so only chunks that were visited are stored, and
+/// their tiles only if they differ from the generated ones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SaveFile {
+    pub version: u32,
+    pub seed: u64,
+    /// chunks are regenerated with the same generator they were generated with first
+    pub generator: MapGeneratorKind,
+    /// game time of the TurnQueue
+    pub time: u64,
+    pub rng: GameRng,
+    pub player: SavedPlayer,
+    /// id of the tile of every TileKind in the chunks, indexed by the kind.
+    /// Kinds follow the order of the tile definitions, which may have changed since saving.
+    pub tile_ids: Vec<String>,
+    pub chunks: Vec<SavedChunk>,
+    /// positions the player has seen before
+    pub fog: Vec<GridPos>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedPlayer {
+    pub pos: GridPos,
+    pub turn_taker: TurnTaker,
+    pub health: Health,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedChunk {
+    pub pos: IVec2,
+    /// None if the tiles are the same as generated from the seed
+    pub tiles: Option<Vec<TileKind>>,
+    pub actors: Vec<StoredActor>,
+}
+
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error("could not access save file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not write save file: {0}")]
+    Serialize(#[from] ron::Error),
+    #[error("could not parse save file: {0}")]
+    Deserialize(#[from] ron::de::SpannedError),
+    #[error("save file has version {found}, but only version {SAVE_VERSION} is supported")]
+    Version { found: u32 },
+    #[error("save file uses tile \"{0}\", which has no definition")]
+    UnknownTile(String),
+    #[error("save file uses tile kind {0}, which is missing from its tile ids")]
+    UnknownTileKind(u16),
+}
+
+impl SaveFile {
+    pub fn write(&self, path: &Path) -> Result<(), SaveError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, ron::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> Result<Self, SaveError> {
+        let save: Self = ron::from_str(&fs::read_to_string(path)?)?;
+        if save.version != SAVE_VERSION {
+            return Err(SaveError::Version {
+                found: save.version,
+            });
+        }
+        Ok(save)
+    }
+
+    /// Convert the saved tile kinds to the kinds of the current tile definitions
+    fn remap_tiles(&mut self, tiles: &TileRegistry) -> Result<(), SaveError> {
+        let kinds = self
+            .tile_ids
+            .iter()
+            .map(|id| {
+                tiles
+                    .kind(id)
+                    .ok_or_else(|| SaveError::UnknownTile(id.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for chunk_tiles in self
+            .chunks
+            .iter_mut()
+            .filter_map(|chunk| chunk.tiles.as_mut())
+        {
+            for kind in chunk_tiles.iter_mut() {
+                *kind = *kinds
+                    .get(kind.0 as usize)
+                    .ok_or(SaveError::UnknownTileKind(kind.0))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<SavePath>()
+        .init_resource::<Permadeath>()
+        .add_event::<SaveGame>()
+        .add_event::<LoadGame>()
+        .add_event::<GameLoaded>()
+        .add_systems(
+            PreUpdate,
+            (save_game, load_game)
+                .chain()
+                .run_if(resource_exists::<TileRegistry>),
+        )
+        .add_systems(
+            Update,
+            delete_save_on_death.run_if(resource_equals(Permadeath(true))),
+        );
+}
+
+pub(super) fn presentation_plugin(app: &mut App) {
+    app.insert_resource(Permadeath(true))
+        .add_systems(Update, read_save_actions.run_if(in_state(Pause::Running)));
+}
+
+fn read_save_actions(
+    action_state: Res<ActionState>,
+    mut save: EventWriter<SaveGame>,
+    mut load: EventWriter<LoadGame>,
+) {
+    if action_state.just_pressed(Action::QuickSave) {
+        save.send(SaveGame);
+    } else if action_state.just_pressed(Action::QuickLoad) {
+        load.send(LoadGame);
+    }
+}
+
+fn save_game(
+    mut events: EventReader<SaveGame>,
+    save_path: Res<SavePath>,
+    seed: Res<WorldSeed>,
+    generator: Res<MapGeneratorKind>,
+    tiles: Res<TileRegistry>,
+    turn_queue: Res<TurnQueue>,
+    game_rng: Res<GameRng>,
+    world_grid: Res<WorldGrid>,
+    chunk_manager: Res<ChunkManager>,
+    fog_of_war: Res<FogOfWar>,
+    player_query: Query<(&GridMovement, &TurnTaker, &Health), (With<Player>, Without<Dead>)>,
+    actors_query: Query<(Entity, &GridMovement, &TurnTaker, Option<&Health>), With<Devil>>,
+    mut log: EventWriter<LogMessage>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+
+    let Ok((grid_movement, turn_taker, health)) = player_query.get_single() else {
+        warn!("can only save while the player is alive");
+        return;
+    };
+
+    let spawned_chunks = chunk_manager.spawned_chunks.iter().filter_map(|chunk_pos| {
+        let (chunk, _) = collect_chunk(*chunk_pos, &world_grid, actors_query.iter())?;
+        Some((*chunk_pos, chunk))
+    });
+    let chunks = spawned_chunks
+        .chain(chunk_manager.stored_chunks())
+        .map(|(pos, chunk)| {
+            let generated = ChunkLayout::generate(&seed, *generator, &tiles, pos);
+            SavedChunk {
+                pos,
+                tiles: (chunk.tiles != generated.tiles).then_some(chunk.tiles),
+                actors: chunk.actors,
+            }
+        })
+        .collect();
+
+    let save = SaveFile {
+        version: SAVE_VERSION,
+        seed: seed.0,
+        generator: *generator,
+        time: turn_queue.time(),
+        rng: game_rng.clone(),
+        player: SavedPlayer {
+            pos: grid_movement
+                .target_pos
+                .unwrap_or(grid_movement.current_pos),
+            turn_taker: turn_taker.clone(),
+            health: *health,
+        },
+        tile_ids: tiles
+            .iter()
+            .map(|(_, definition)| definition.id.clone())
+            .collect(),
+        chunks,
+        fog: fog_of_war.viewed_positions().iter().copied().collect(),
+    };
+
+    match save.write(&save_path.0) {
+        Ok(()) => {
+            log.send(LogMessage::new(
+                LogKind::System,
+                format!("Saved the game to {}.", save_path.0.display()),
+            ));
+        }
+        Err(err) => error!("{err}"),
+    }
+}
+
+fn load_game(
+    mut commands: Commands,
+    mut events: EventReader<LoadGame>,
+    save_path: Res<SavePath>,
+    tiles: Res<TileRegistry>,
+    mut seed: ResMut<WorldSeed>,
+    mut generator: ResMut<MapGeneratorKind>,
+    mut turn_queue: ResMut<TurnQueue>,
+    mut game_rng: ResMut<GameRng>,
+    mut world_grid: ResMut<WorldGrid>,
+    mut occupancy: ResMut<Occupancy>,
+    mut chunk_manager: ResMut<ChunkManager>,
+    mut fog_of_war: ResMut<FogOfWar>,
+    mut player_query: Query<
+        (
+            Entity,
+            &mut Transform,
+            &mut GridMovement,
+            &mut TurnTaker,
+            &mut Health,
+        ),
+        With<Player>,
+    >,
+    despawn_query: Query<Entity, Or<(With<TileStorage>, With<Devil>)>>,
+    mut loaded: EventWriter<GameLoaded>,
+    mut log: EventWriter<LogMessage>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+
+    let save = SaveFile::read(&save_path.0).and_then(|mut save| {
+        save.remap_tiles(&tiles)?;
+        Ok(save)
+    });
+    let save = match save {
+        Ok(save) => save,
+        Err(err) => {
+            error!("{err}");
+            return;
+        }
+    };
+
+    let Ok((player, mut transform, mut grid_movement, mut turn_taker, mut health)) =
+        player_query.get_single_mut()
+    else {
+        return;
+    };
+
+    // throw away the current world, chunks are spawned again from the chunk manager
+    for entity in despawn_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    world_grid.clear();
+    occupancy.clear();
+    *seed = WorldSeed(save.seed);
+    chunk_manager.start_run(&seed);
+    *generator = save.generator;
+    for chunk in save.chunks {
+        let tiles = chunk
+            .tiles
+            .unwrap_or_else(|| ChunkLayout::generate(&seed, *generator, &tiles, chunk.pos).tiles);
+        // a save can contain more chunks than the memory budget allows, none of them may be lost
+        chunk_manager.restore(
+            chunk.pos,
+            StoredChunk {
+                tiles,
+                actors: chunk.actors,
+            },
+        );
+    }
+    fog_of_war.set_viewed_positions(save.fog);
+    turn_queue.reset(save.time);
+    *game_rng = save.rng;
+
+    let world_pos = save.player.pos.to_world_pos();
+    transform.translation.x = world_pos.x;
+    transform.translation.y = world_pos.y;
+    *grid_movement = GridMovement {
+        current_pos: save.player.pos,
+        target_pos: None,
+    };
+    *turn_taker = save.player.turn_taker;
+    *health = save.player.health;
+    occupancy.place(player, save.player.pos, None);
+    commands
+        .entity(player)
+        .remove::<(Dead, MovementPlan, Resting)>();
+
+    log.send(LogMessage::new(
+        LogKind::System,
+        format!("Loaded the game from {}.", save_path.0.display()),
+    ));
+    loaded.send(GameLoaded);
+}
+
+/// A run ends with the player, so does its save
+fn delete_save_on_death(mut deaths: EventReader<PlayerDied>, save_path: Res<SavePath>) {
+    if deaths.read().count() == 0 || !save_path.0.exists() {
+        return;
+    }
+
+    match fs::remove_file(&save_path.0) {
+        Ok(()) => info!("deleted save {}", save_path.0.display()),
+        Err(err) => error!("could not delete save {}: {err}", save_path.0.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::tiles::TileDefinition;
+
+    fn tile_registry(ids: &[&str]) -> TileRegistry {
+        let definitions = ids
+            .iter()
+            .map(|id| {
+                ron::from_str::<TileDefinition>(&format!(
+                    r#"(id: "{id}", name: "{id}", atlas_index: 0, walkable: true,
+                        transparent: true, movement_cost: 1)"#
+                ))
+                .unwrap()
+            })
+            .collect();
+        TileRegistry::new(definitions)
+    }
+
+    fn save_with_tiles(tile_ids: Vec<String>, tiles: Vec<TileKind>) -> SaveFile {
+        SaveFile {
+            version: SAVE_VERSION,
+            seed: 0,
+            generator: MapGeneratorKind::default(),
+            time: 0,
+            rng: GameRng::default(),
+            player: SavedPlayer {
+                pos: GridPos { x: 0, y: 0 },
+                turn_taker: TurnTaker::default(),
+                health: Health::new(10),
+            },
+            tile_ids,
+            chunks: vec![SavedChunk {
+                pos: IVec2::ZERO,
+                tiles: Some(tiles),
+                actors: Vec::new(),
+            }],
+            fog: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn saved_tiles_follow_reordered_definitions() {
+        let saved_with = tile_registry(&["floor", "wall"]);
+        let loaded_with = tile_registry(&["bush", "wall", "floor"]);
+        let floor = saved_with.kind("floor").unwrap();
+        let wall = saved_with.kind("wall").unwrap();
+        let tile_ids = saved_with
+            .iter()
+            .map(|(_, definition)| definition.id.clone())
+            .collect();
+        let mut save = save_with_tiles(tile_ids, vec![floor, wall, wall]);
+
+        save.remap_tiles(&loaded_with).unwrap();
+
+        let floor = loaded_with.kind("floor").unwrap();
+        let wall = loaded_with.kind("wall").unwrap();
+        assert_eq!(save.chunks[0].tiles, Some(vec![floor, wall, wall]));
+    }
+
+    #[test]
+    fn saved_tiles_without_definition_are_rejected() {
+        let mut save = save_with_tiles(vec!["lava".to_string()], vec![TileKind(0)]);
+
+        let result = save.remap_tiles(&tile_registry(&["floor"]));
+
+        assert!(matches!(result, Err(SaveError::UnknownTile(id)) if id == "lava"));
+    }
+}
@@ -0,0 +1,332 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    prelude::*,
+    utils::HashMap,
+};
+use bevy_ecs_tilemap::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub const TILE_DEFINITIONS_PATH: &str = "data/map.tiles.ron";
+
+/// Kind of a tile, stored as a component on each tile entity.
+/// Look up its properties in the TileRegistry.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct TileKind(pub u16);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TileDefinition {
+    /// stable identifier used to reference this kind from code, e.g. "wall"
+    pub id: String,
+    /// display name of the tile
+    pub name: String,
+    /// index of the tile sprite in images/atlas.png
+    pub atlas_index: u32,
+    pub walkable: bool,
+    pub transparent: bool,
+    /// cost of stepping onto this tile for pathfinding, at least 1
+    pub movement_cost: u32,
+    /// color of the tile on the minimap
+    #[serde(default = "default_map_color")]
+    pub map_color: (u8, u8, u8),
+    /// free-form tags for behaviour that has no field of its own, e.g. "solid"
+    #[serde(default)]
+    pub flags: Vec<String>,
+}
+
+fn default_map_color() -> (u8, u8, u8) {
+    (128, 128, 128)
+}
+
+impl TileDefinition {
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|f| f == flag)
+    }
+}
+
+/// Tile definitions as they are loaded from a `.tiles.ron` asset
+#[derive(Asset, TypePath, Debug, Deserialize)]
+pub struct TileDefinitions {
+    pub tiles: Vec<TileDefinition>,
+}
+
+/// All tile kinds of the game.
+/// Only inserted once the tile definitions asset finished loading.
+#[derive(Resource, Debug)]
+pub struct TileRegistry {
+    definitions: Vec<TileDefinition>,
+    kinds_by_id: HashMap<String, TileKind>,
+    /// returned for kinds without a definition, a solid wall nobody can see through
+    unknown: TileDefinition,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TileRegistryError {
+    #[error("tile \"{0}\" is used by the map but has no definition anymore")]
+    MissingTile(String),
+}
+
+impl TileRegistry {
+    pub fn new(definitions: Vec<TileDefinition>) -> Self {
+        let kinds_by_id = definitions
+            .iter()
+            .enumerate()
+            .map(|(index, definition)| (definition.id.clone(), TileKind(index as u16)))
+            .collect();
+
+        Self {
+            definitions,
+            kinds_by_id,
+            unknown: TileDefinition {
+                id: "unknown".to_string(),
+                name: "Unknown".to_string(),
+                atlas_index: 0,
+                walkable: false,
+                transparent: false,
+                movement_cost: 1,
+                map_color: default_map_color(),
+                flags: vec!["solid".to_string()],
+            },
+        }
+    }
+
+    /// Build a registry from reloaded definitions, without changing the TileKind of tiles that
+    /// are still defined. Kinds are stored all over the map, so they have to stay the same no
+    /// matter how the definitions are ordered. New tiles get new kinds after the existing ones.
+    pub fn reload(&self, definitions: Vec<TileDefinition>) -> Result<Self, TileRegistryError> {
+        let mut by_id: HashMap<String, TileDefinition> = definitions
+            .into_iter()
+            .map(|definition| (definition.id.clone(), definition))
+            .collect();
+
+        let mut reloaded = Vec::with_capacity(by_id.len());
+        for definition in &self.definitions {
+            let Some(definition) = by_id.remove(&definition.id) else {
+                return Err(TileRegistryError::MissingTile(definition.id.clone()));
+            };
+            reloaded.push(definition);
+        }
+        let mut added: Vec<TileDefinition> = by_id.into_values().collect();
+        added.sort_by(|a, b| a.id.cmp(&b.id));
+        reloaded.extend(added);
+
+        Ok(Self::new(reloaded))
+    }
+
+    /// The definition of a kind, or an unwalkable placeholder if the kind is not defined
+    pub fn get(&self, kind: TileKind) -> &TileDefinition {
+        self.definitions
+            .get(kind.0 as usize)
+            .unwrap_or(&self.unknown)
+    }
+
+    pub fn kind(&self, id: &str) -> Option<TileKind> {
+        self.kinds_by_id.get(id).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (TileKind, &TileDefinition)> {
+        self.definitions
+            .iter()
+            .enumerate()
+            .map(|(index, definition)| (TileKind(index as u16), definition))
+    }
+}
+
+#[derive(Resource)]
+struct TileDefinitionsHandle(Handle<TileDefinitions>);
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_asset::<TileDefinitions>()
+        .register_asset_loader(TileDefinitionsLoader)
+        .add_systems(Startup, load_tile_definitions)
+        .add_systems(
+            Update,
+            (
+                update_tile_registry,
+                update_tile_textures.run_if(resource_exists_and_changed::<TileRegistry>),
+            )
+                .chain(),
+        );
+}
+
+fn load_tile_definitions(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(TileDefinitionsHandle(
+        asset_server.load(TILE_DEFINITIONS_PATH),
+    ));
+}
+
+/// (Re)build the TileRegistry whenever the definitions finished loading or were hot reloaded.
+/// A reload that removes tiles is rejected, the map may still contain them.
+fn update_tile_registry(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<TileDefinitions>>,
+    handle: Res<TileDefinitionsHandle>,
+    definitions: Res<Assets<TileDefinitions>>,
+    registry: Option<Res<TileRegistry>>,
+) {
+    for event in events.read() {
+        if !event.is_loaded_with_dependencies(&handle.0) && !event.is_modified(&handle.0) {
+            continue;
+        }
+
+        let Some(definitions) = definitions.get(&handle.0) else {
+            continue;
+        };
+
+        let Some(registry) = &registry else {
+            commands.insert_resource(TileRegistry::new(definitions.tiles.clone()));
+            continue;
+        };
+        match registry.reload(definitions.tiles.clone()) {
+            Ok(reloaded) => commands.insert_resource(reloaded),
+            Err(err) => error!("ignoring reloaded tile definitions: {err}"),
+        }
+    }
+}
+
+/// keep the sprites of spawned tiles in sync with their definitions
+fn update_tile_textures(
+    registry: Res<TileRegistry>,
+    mut tile_query: Query<(&TileKind, &mut TileTextureIndex, &mut Name)>,
+) {
+    for (kind, mut texture_index, mut name) in tile_query.iter_mut() {
+        let definition = registry.get(*kind);
+        texture_index.0 = definition.atlas_index;
+        name.set(definition.name.clone());
+    }
+}
+
+#[derive(Default)]
+struct TileDefinitionsLoader;
+
+#[derive(Debug, Error)]
+enum TileDefinitionsLoaderError {
+    #[error("could not read tile definitions: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse tile definitions: {0}")]
+    Ron(#[from] ron::de::SpannedError),
+    #[error("tile \"{id}\" has a movement cost of {cost}, it has to be at least 1")]
+    MovementCost { id: String, cost: u32 },
+}
+
+/// Parse tile definitions and check that pathfinding can work with them
+fn parse_tile_definitions(bytes: &[u8]) -> Result<TileDefinitions, TileDefinitionsLoaderError> {
+    let definitions: TileDefinitions = ron::de::from_bytes(bytes)?;
+    // A* assumes every step costs at least as much as its heuristic, which counts steps
+    if let Some(tile) = definitions.tiles.iter().find(|tile| tile.movement_cost < 1) {
+        return Err(TileDefinitionsLoaderError::MovementCost {
+            id: tile.id.clone(),
+            cost: tile.movement_cost,
+        });
+    }
+    Ok(definitions)
+}
+
+impl AssetLoader for TileDefinitionsLoader {
+    type Asset = TileDefinitions;
+    type Settings = ();
+    type Error = TileDefinitionsLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        parse_tile_definitions(&bytes)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tiles.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(id: &str, walkable: bool) -> TileDefinition {
+        TileDefinition {
+            id: id.to_string(),
+            name: id.to_string(),
+            atlas_index: 0,
+            walkable,
+            transparent: true,
+            movement_cost: 1,
+            map_color: default_map_color(),
+            flags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reload_keeps_existing_kinds() {
+        let registry =
+            TileRegistry::new(vec![definition("floor", true), definition("wall", false)]);
+        let wall = registry.kind("wall").unwrap();
+
+        let reloaded = registry
+            .reload(vec![
+                definition("bush", true),
+                definition("wall", false),
+                definition("floor", true),
+            ])
+            .unwrap();
+
+        assert_eq!(reloaded.kind("wall"), Some(wall));
+        assert_eq!(reloaded.get(wall).id, "wall");
+        assert_eq!(reloaded.kind("bush"), Some(TileKind(2)));
+    }
+
+    #[test]
+    fn reload_rejects_removed_tiles() {
+        let registry =
+            TileRegistry::new(vec![definition("floor", true), definition("wall", false)]);
+
+        let result = registry.reload(vec![definition("floor", true)]);
+
+        assert_eq!(
+            result.err(),
+            Some(TileRegistryError::MissingTile("wall".to_string()))
+        );
+    }
+
+    #[test]
+    fn unknown_kinds_are_not_walkable() {
+        let registry = TileRegistry::new(vec![definition("floor", true)]);
+
+        assert!(!registry.get(TileKind(7)).walkable);
+    }
+
+    #[test]
+    fn flags_are_loaded_from_the_definitions() {
+        let definitions: TileDefinitions =
+            ron::from_str(include_str!("../../assets/data/map.tiles.ron")).unwrap();
+        let registry = TileRegistry::new(definitions.tiles);
+
+        let wall = registry.get(registry.kind("wall").unwrap());
+        let floor = registry.get(registry.kind("floor").unwrap());
+        assert!(wall.has_flag("solid"));
+        assert!(!floor.has_flag("solid"));
+    }
+
+    #[test]
+    fn free_movement_is_rejected() {
+        let result = parse_tile_definitions(
+            br#"(tiles: [(
+                id: "ice",
+                name: "Ice",
+                atlas_index: 0,
+                walkable: true,
+                transparent: true,
+                movement_cost: 0,
+            )])"#,
+        );
+
+        assert!(matches!(
+            result,
+            Err(TileDefinitionsLoaderError::MovementCost { id, cost: 0 }) if id == "ice"
+        ));
+    }
+}
@@ -1,7 +1,19 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::states::Pause;
 
-use super::player::Player;
+use super::{map::GridPos, player::Player, tiles::TileRegistry, world_grid::WorldGrid};
 
+/// Game time a standard action takes for an actor with NORMAL_SPEED
+pub const ACTION_COST: u32 = 100;
+pub const NORMAL_SPEED: u32 = 100;
+
+/// Whose turn it is, derived from the TurnQueue.
+/// Player while the player is the current actor of the queue, Environment while other actors
+/// ahead of it take their turns.
 #[derive(States, Hash, Debug, Clone, Eq, PartialEq, Default)]
 pub enum TurnState {
     #[default]
@@ -9,56 +21,204 @@ pub enum TurnState {
     Environment,
 }
 
-#[derive(Component)]
+/// An actor that takes turns.
+/// Actions push the next turn back by their cost, scaled by the actor's speed.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct TurnTaker {
-    pub actions_per_turn: u32,
-    pub actions_remaining: u32,
+    /// NORMAL_SPEED acts once per ACTION_COST, twice as fast acts twice as often
+    pub speed: u32,
+    /// game time at which this actor acts next
+    pub next_turn: u64,
 }
 
 impl Default for TurnTaker {
     fn default() -> Self {
+        Self::new(NORMAL_SPEED)
+    }
+}
+
+impl TurnTaker {
+    pub fn new(speed: u32) -> Self {
         Self {
-            actions_per_turn: 1,
-            actions_remaining: 1,
+            speed,
+            next_turn: 0,
         }
     }
+
+    /// Pay for an action that costs `cost` game time at NORMAL_SPEED
+    pub fn spend(&mut self, cost: u32) {
+        self.next_turn += self.scaled_cost(cost);
+    }
+
+    /// Standard actions this actor takes per turn, at least one
+    pub fn actions_per_turn(&self) -> u32 {
+        (self.speed / NORMAL_SPEED).max(1)
+    }
+
+    /// Standard actions this actor can still take before the game time reaches `time`
+    pub fn actions_before(&self, time: u64) -> u32 {
+        let action_cost = self.scaled_cost(ACTION_COST).max(1);
+        time.saturating_sub(self.next_turn).div_ceil(action_cost) as u32
+    }
+
+    fn scaled_cost(&self, cost: u32) -> u64 {
+        cost as u64 * NORMAL_SPEED as u64 / self.speed.max(1) as u64
+    }
+}
+
+/// Cost of stepping onto a position, standard actions times the tile's movement cost
+pub fn move_cost(pos: &GridPos, world_grid: &WorldGrid, tiles: &TileRegistry) -> u32 {
+    let movement_cost = world_grid
+        .kind(pos)
+        .map_or(1, |kind| tiles.get(kind).movement_cost);
+    ACTION_COST * movement_cost
+}
+
+/// Decides who acts next.
+/// Every TurnTaker is queued at its next turn, the game time only moves forward once everyone
+/// who can act at the current time did so.
+#[derive(Resource, Debug, Default)]
+pub struct TurnQueue {
+    time: u64,
+    /// (next turn, queued order, actor), actors with the same next turn act in the order they
+    /// were queued in. Entries of actors that acted or despawned since are skipped.
+    queue: BinaryHeap<Reverse<(u64, u64, Entity)>>,
+    /// number of entries queued so far
+    queued: u64,
+}
+
+impl TurnQueue {
+    /// Current game time
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    /// Number of the turn the game time is in, a turn lasts ACTION_COST game time
+    pub fn turn(&self) -> u64 {
+        self.time / ACTION_COST as u64
+    }
+
+    /// Game time at which the next turn starts
+    pub fn turn_end(&self) -> u64 {
+        (self.turn() + 1) * ACTION_COST as u64
+    }
+
+    /// Start over at `time`, forgetting every queued actor.
+    /// Actors are queued again once their TurnTaker changes or they spawn.
+    pub fn reset(&mut self, time: u64) {
+        self.time = time;
+        self.queue.clear();
+    }
+
+    /// Whether the actor's turn has come
+    pub fn can_act(&self, turn_taker: &TurnTaker) -> bool {
+        turn_taker.next_turn <= self.time
+    }
+
+    /// The actor whose turn it is and the next turn it was queued at.
+    /// None if everyone who can act at the current time did so.
+    pub fn current(&self) -> Option<(Entity, u64)> {
+        let Reverse((next_turn, _, entity)) = *self.queue.peek()?;
+        (next_turn <= self.time).then_some((entity, next_turn))
+    }
+
+    /// Take the current actor out of the queue, it is queued again once its TurnTaker changes
+    pub fn pop(&mut self) -> Option<(Entity, u64)> {
+        let current = self.current()?;
+        self.queue.pop();
+        Some(current)
+    }
+
+    fn push(&mut self, entity: Entity, next_turn: u64) {
+        self.queue.push(Reverse((next_turn, self.queued, entity)));
+        self.queued += 1;
+    }
 }
 
 pub(super) fn plugin(app: &mut App) {
     app.insert_state(TurnState::default())
-        .add_systems(PostUpdate, advance_turn_state);
+        .init_resource::<TurnQueue>()
+        .add_systems(
+            PostUpdate,
+            (
+                schedule_turns.run_if(in_state(Pause::Running)),
+                update_turn_state,
+            )
+                .chain(),
+        );
 }
 
-fn advance_turn_state(
+fn schedule_turns(mut turn_queue: ResMut<TurnQueue>, mut actors: Query<(Entity, &mut TurnTaker)>) {
+    let time = turn_queue.time;
+
+    // queue actors that acted or just spawned at their next turn
+    for (entity, mut turn_taker) in actors.iter_mut() {
+        if !turn_taker.is_changed() {
+            continue;
+        }
+
+        // actors restored from a stored chunk can't act in the past
+        if turn_taker.next_turn < time {
+            turn_taker.bypass_change_detection().next_turn = time;
+        }
+        turn_queue.push(entity, turn_taker.next_turn);
+    }
+
+    while let Some(Reverse((next_turn, _, entity))) = turn_queue.queue.peek().copied() {
+        let is_current = actors
+            .get(entity)
+            .is_ok_and(|(_, turn_taker)| turn_taker.next_turn == next_turn);
+        if is_current {
+            break;
+        }
+        turn_queue.queue.pop();
+    }
+
+    // only advance the time once nobody can act anymore
+    if let Some(Reverse((next_turn, _, _))) = turn_queue.queue.peek().copied()
+        && next_turn > turn_queue.time
+    {
+        turn_queue.time = next_turn;
+    }
+}
+
+fn update_turn_state(
     turn_state: Res<State<TurnState>>,
     mut next_turn_state: ResMut<NextState<TurnState>>,
-    mut player_query: Query<&mut TurnTaker, With<Player>>,
-    mut environment_query: Query<&mut TurnTaker, Without<Player>>,
+    turn_queue: Res<TurnQueue>,
+    player_query: Query<Entity, With<Player>>,
 ) {
-    match turn_state.get() {
-        TurnState::Player => {
-            let Ok(mut player) = player_query.get_single_mut() else {
-                next_turn_state.set(TurnState::Environment);
-                return;
-            };
-
-            if player.actions_remaining <= 0 {
-                next_turn_state.set(TurnState::Environment);
-                player.actions_remaining = player.actions_per_turn;
-            }
-        }
-        TurnState::Environment => {
-            let all_actions_taken = environment_query
-                .iter()
-                .all(|turn_taker| turn_taker.actions_remaining <= 0);
-
-            if all_actions_taken || environment_query.is_empty() {
-                // Reset all environment actions and switch to player
-                for mut turn_taker in environment_query.iter_mut() {
-                    turn_taker.actions_remaining = turn_taker.actions_per_turn;
-                }
-                next_turn_state.set(TurnState::Player);
-            }
-        }
+    let player_is_current = player_query.get_single().is_ok_and(|player| {
+        turn_queue
+            .current()
+            .is_some_and(|(entity, _)| entity == player)
+    });
+    let state = if player_is_current {
+        TurnState::Player
+    } else {
+        TurnState::Environment
+    };
+
+    if *turn_state.get() != state {
+        next_turn_state.set(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn actors_due_at_the_same_time_act_in_queued_order() {
+        let [first, second, third] = [1, 2, 3].map(Entity::from_raw);
+        let mut turn_queue = TurnQueue::default();
+        turn_queue.push(second, 0);
+        turn_queue.push(third, ACTION_COST as u64);
+        turn_queue.push(first, 0);
+
+        assert_eq!(turn_queue.pop(), Some((second, 0)));
+        assert_eq!(turn_queue.pop(), Some((first, 0)));
+        // nobody else can act before the time moves on
+        assert_eq!(turn_queue.pop(), None);
     }
 }
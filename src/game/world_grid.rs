@@ -0,0 +1,110 @@
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ecs_tilemap::prelude::*;
+
+use super::{
+    map::{CHUNK_SIZE, GameGrid, GridPos},
+    tiles::TileKind,
+};
+
+/// A spawned tile, as stored in the WorldGrid
+#[derive(Clone, Copy, Debug)]
+pub struct WorldTile {
+    /// the tilemap entity of the chunk the tile belongs to
+    pub chunk: Entity,
+    pub tile_pos: TilePos,
+    pub entity: Entity,
+    pub kind: TileKind,
+}
+
+/// Spatial index of all spawned tiles by their grid position.
+/// Kept up to date when chunks are spawned and despawned, so tile lookups don't have to search
+/// through every chunk.
+#[derive(Resource, Default, Debug)]
+pub struct WorldGrid {
+    tiles: HashMap<GridPos, WorldTile>,
+    /// increased on every change to the grid
+    revision: u64,
+}
+
+impl WorldGrid {
+    pub fn get(&self, pos: &GridPos) -> Option<&WorldTile> {
+        self.tiles.get(pos)
+    }
+
+    pub fn kind(&self, pos: &GridPos) -> Option<TileKind> {
+        self.tiles.get(pos).map(|tile| tile.kind)
+    }
+
+    pub fn insert(&mut self, pos: GridPos, tile: WorldTile) {
+        self.tiles.insert(pos, tile);
+        self.revision += 1;
+    }
+
+    pub fn set_kind(&mut self, pos: &GridPos, kind: TileKind) {
+        if let Some(tile) = self.tiles.get_mut(pos)
+            && tile.kind != kind
+        {
+            tile.kind = kind;
+            self.revision += 1;
+        }
+    }
+
+    /// Remove all tiles of the chunk at `chunk_pos`
+    pub fn remove_chunk(&mut self, chunk_pos: IVec2) {
+        for y in 0..CHUNK_SIZE.y {
+            for x in 0..CHUNK_SIZE.x {
+                let pos = GameGrid::tile_pos_to_grid_pos(chunk_pos, TilePos { x, y });
+                self.tiles.remove(&pos);
+            }
+        }
+        self.revision += 1;
+    }
+
+    /// Remove every tile, e.g. before loading a save game
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+        self.revision += 1;
+    }
+
+    /// A number that changes whenever a tile is added, removed or changes its kind.
+    /// Use it to invalidate anything derived from the grid.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&GridPos, &WorldTile)> {
+        self.tiles.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+}
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<WorldGrid>()
+        .add_systems(PostUpdate, sync_changed_tile_kinds);
+}
+
+/// keep the WorldGrid in sync when a tile entity changes its kind
+fn sync_changed_tile_kinds(
+    mut world_grid: ResMut<WorldGrid>,
+    tile_query: Query<(&TileKind, &TilePos, &TilemapId), Changed<TileKind>>,
+    chunks_query: Query<&Transform, With<TileStorage>>,
+) {
+    for (kind, tile_pos, tilemap_id) in tile_query.iter() {
+        let Ok(chunk_transform) = chunks_query.get(tilemap_id.0) else {
+            continue;
+        };
+
+        let chunk_pos = GameGrid::grid_pos_to_chunk_pos(GridPos::from_world_pos(
+            chunk_transform.translation.xy(),
+        ));
+        let pos = GameGrid::tile_pos_to_grid_pos(chunk_pos, *tile_pos);
+        world_grid.set_kind(&pos, *kind);
+    }
+}
@@ -1,14 +1,16 @@
-mod components;
+pub mod components;
 #[cfg(feature = "dev")]
 mod dev_tools;
-mod game;
-mod resources;
+pub mod game;
+pub mod resources;
 mod screens;
-mod states;
+pub mod states;
+mod theme;
 
 use bevy::{
     audio::{AudioPlugin, Volume},
     prelude::*,
+    state::app::StatesPlugin,
 };
 
 pub struct AppPlugin;
@@ -38,10 +40,38 @@ impl Plugin for AppPlugin {
         app.insert_resource(ClearColor(Color::srgb(0., 0., 0.)));
 
         // Add other plugins.
-        app.add_plugins((game::plugin, screens::plugin));
+        app.add_plugins((game::plugin, screens::plugin, theme::plugin));
 
         // Enable dev tools for dev builds.
         #[cfg(feature = "dev")]
         app.add_plugins(dev_tools::plugin);
     }
 }
+
+/// Runs map generation, turns, fov and ai without a window or GPU.
+/// Useful for integration tests and balance runs, the player is controlled by sending
+/// `PlayerAction` events. Insert a `SelectedGenerator` or `WorldSeed` first to choose the world:
+/// ```no_run
+/// use bevy::prelude::*;
+/// use m_rouge::{
+///     SimulationPlugin,
+///     game::{
+///         map::WorldSeed,
+///         map_generator::{MapGeneratorKind, SelectedGenerator},
+///     },
+/// };
+///
+/// App::new()
+///     .insert_resource(WorldSeed(42))
+///     .insert_resource(SelectedGenerator(MapGeneratorKind::Caves))
+///     .add_plugins(SimulationPlugin)
+///     .run();
+/// ```
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((MinimalPlugins, StatesPlugin, AssetPlugin::default()));
+        app.add_plugins(game::headless_plugin);
+    }
+}
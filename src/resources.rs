@@ -1 +1 @@
-pub use crate::game::map::HoveredTilePos;
+pub use crate::game::map::{HoveredTilePos, WorldSeed};
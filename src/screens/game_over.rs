@@ -0,0 +1,58 @@
+//! Shown after the player died.
+
+use bevy::prelude::*;
+
+use crate::{
+    game::input::{Action, ActionState},
+    states::Screen,
+    theme::{ButtonPressed, Widgets, ui_root},
+};
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum GameOverButton {
+    TryAgain,
+    Title,
+}
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(OnEnter(Screen::GameOver), spawn_game_over_screen)
+        .add_systems(
+            Update,
+            handle_game_over_buttons.run_if(in_state(Screen::GameOver)),
+        );
+}
+
+fn spawn_game_over_screen(mut commands: Commands) {
+    commands
+        .spawn((ui_root("Game Over Screen"), StateScoped(Screen::GameOver)))
+        .with_children(|children| {
+            children.header("Game Over");
+            children
+                .button("Try again")
+                .insert(GameOverButton::TryAgain);
+            children.button("Title").insert(GameOverButton::Title);
+        });
+}
+
+fn handle_game_over_buttons(
+    mut presses: EventReader<ButtonPressed>,
+    button_query: Query<&GameOverButton>,
+    action_state: Res<ActionState>,
+    mut next_screen: ResMut<NextState<Screen>>,
+) {
+    // going back leads to the title screen
+    if action_state.just_pressed(Action::Cancel) {
+        next_screen.set(Screen::Title);
+    }
+
+    for ButtonPressed(entity) in presses.read() {
+        let Ok(button) = button_query.get(*entity) else {
+            continue;
+        };
+
+        next_screen.set(match button {
+            GameOverButton::TryAgain => Screen::Loading,
+            GameOverButton::Title => Screen::Title,
+        });
+    }
+}
@@ -1,5 +1,178 @@
+//! The screen the game is played on, with the HUD and a pause menu on top of it.
+
 use bevy::prelude::*;
 
-pub(super) fn plugin(_app: &mut App) {
-    // TODO: add ui screens here - see bevy 2d example for structure
+use crate::{
+    components::{Player, TurnTaker},
+    game::{
+        combat::Health,
+        input::{Action, ActionState},
+        map::GridMovement,
+        turns::TurnQueue,
+    },
+    states::{Pause, Screen, TurnState},
+    theme::{ButtonPressed, OVERLAY_BACKGROUND, Widgets, ui_root},
+};
+
+/// A line of the HUD and what it shows
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum HudText {
+    Health,
+    TurnState,
+    Actions,
+    Turn,
+    Position,
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum PauseButton {
+    Continue,
+    Title,
+}
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(OnEnter(Screen::Gameplay), spawn_hud)
+        .add_systems(OnEnter(Pause::Paused), (pause_time, spawn_pause_menu))
+        .add_systems(OnExit(Pause::Paused), unpause_time)
+        .add_systems(
+            Update,
+            (
+                toggle_pause.run_if(in_state(Screen::Gameplay)),
+                handle_pause_buttons.run_if(in_state(Pause::Paused)),
+                update_hud.run_if(in_state(Screen::Gameplay)),
+            ),
+        );
+}
+
+fn spawn_hud(mut commands: Commands) {
+    commands
+        .spawn((
+            Name::new("HUD"),
+            Node {
+                position_type: PositionType::Absolute,
+                top: Val::Px(12.0),
+                left: Val::Px(12.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.0),
+                ..default()
+            },
+            StateScoped(Screen::Gameplay),
+        ))
+        .with_children(|children| {
+            for hud_text in [
+                HudText::Health,
+                HudText::TurnState,
+                HudText::Actions,
+                HudText::Turn,
+                HudText::Position,
+            ] {
+                children.label("").insert(hud_text);
+            }
+        });
+}
+
+/// Rewrite the HUD whenever anything it shows changed
+fn update_hud(
+    turn_state: Res<State<TurnState>>,
+    turn_queue: Res<TurnQueue>,
+    player_query: Query<(Ref<TurnTaker>, Ref<GridMovement>, Ref<Health>), With<Player>>,
+    mut text_query: Query<(Ref<HudText>, &mut Text)>,
+) {
+    let Ok((turn_taker, grid_movement, health)) = player_query.get_single() else {
+        return;
+    };
+
+    let hud_added = text_query.iter().any(|(hud_text, _)| hud_text.is_added());
+    if !hud_added
+        && !turn_state.is_changed()
+        && !turn_queue.is_changed()
+        && !turn_taker.is_changed()
+        && !grid_movement.is_changed()
+        && !health.is_changed()
+    {
+        return;
+    }
+
+    for (hud_text, mut text) in text_query.iter_mut() {
+        text.0 = match *hud_text {
+            HudText::Health => format!("HP {}/{}", health.current, health.max),
+            HudText::TurnState => match turn_state.get() {
+                TurnState::Player => "Your turn".to_string(),
+                TurnState::Environment => "Enemies' turn".to_string(),
+            },
+            HudText::Actions => format!(
+                "Actions {}/{}",
+                turn_taker.actions_before(turn_queue.turn_end()),
+                turn_taker.actions_per_turn()
+            ),
+            HudText::Turn => format!("Turn {}", turn_queue.turn()),
+            HudText::Position => format!(
+                "Position {}, {}",
+                grid_movement.current_pos.x, grid_movement.current_pos.y
+            ),
+        };
+    }
+}
+
+fn toggle_pause(
+    action_state: Res<ActionState>,
+    pause: Res<State<Pause>>,
+    mut next_pause: ResMut<NextState<Pause>>,
+) {
+    if !action_state.just_pressed(Action::Pause) {
+        return;
+    }
+
+    next_pause.set(match pause.get() {
+        Pause::Running => Pause::Paused,
+        Pause::Paused => Pause::Running,
+    });
+}
+
+/// animations and camera movement stand still while paused
+fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn unpause_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn spawn_pause_menu(mut commands: Commands) {
+    commands
+        .spawn((
+            ui_root("Pause Menu"),
+            BackgroundColor(OVERLAY_BACKGROUND),
+            StateScoped(Pause::Paused),
+        ))
+        .with_children(|children| {
+            children.header("Paused");
+            children.button("Continue").insert(PauseButton::Continue);
+            children.button("Title").insert(PauseButton::Title);
+        });
+}
+
+fn handle_pause_buttons(
+    mut presses: EventReader<ButtonPressed>,
+    button_query: Query<&PauseButton>,
+    action_state: Res<ActionState>,
+    mut next_pause: ResMut<NextState<Pause>>,
+    mut next_screen: ResMut<NextState<Screen>>,
+) {
+    // going back continues the game
+    if action_state.just_pressed(Action::Cancel) {
+        next_pause.set(Pause::Running);
+    }
+
+    for ButtonPressed(entity) in presses.read() {
+        let Ok(button) = button_query.get(*entity) else {
+            continue;
+        };
+
+        match button {
+            PauseButton::Continue => next_pause.set(Pause::Running),
+            // leaving the Gameplay screen tears down the run
+            PauseButton::Title => next_screen.set(Screen::Title),
+        }
+    }
 }
@@ -0,0 +1,54 @@
+//! Shown until everything a run needs is loaded.
+
+use bevy::prelude::*;
+
+use crate::{
+    game::{assets::GameAssets, tiles::TileRegistry},
+    states::Screen,
+    theme::{Widgets, ui_root},
+};
+
+#[derive(Component)]
+struct LoadingProgressText;
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(OnEnter(Screen::Loading), spawn_loading_screen)
+        .add_systems(
+            Update,
+            (update_loading_progress, continue_to_gameplay)
+                .chain()
+                .run_if(in_state(Screen::Loading)),
+        );
+}
+
+fn spawn_loading_screen(mut commands: Commands) {
+    commands
+        .spawn((ui_root("Loading Screen"), StateScoped(Screen::Loading)))
+        .with_children(|children| {
+            children.label("Loading...").insert(LoadingProgressText);
+        });
+}
+
+fn update_loading_progress(
+    assets: Res<GameAssets>,
+    asset_server: Res<AssetServer>,
+    mut text_query: Query<&mut Text, With<LoadingProgressText>>,
+) {
+    let (loaded, total) = assets.progress(&asset_server);
+
+    for mut text in text_query.iter_mut() {
+        text.0 = format!("Loading... {loaded}/{total}");
+    }
+}
+
+fn continue_to_gameplay(
+    assets: Res<GameAssets>,
+    asset_server: Res<AssetServer>,
+    tiles: Option<Res<TileRegistry>>,
+    mut next_screen: ResMut<NextState<Screen>>,
+) {
+    // the TileRegistry is inserted a moment after its definitions finished loading
+    if tiles.is_some() && assets.is_loaded(&asset_server) {
+        next_screen.set(Screen::Gameplay);
+    }
+}
@@ -1,13 +1,23 @@
 //! The game's main screen states and transitions between them.
 
+mod game_over;
 mod gameplay;
+mod loading;
+mod title;
 
-use crate::states::Screen;
+use crate::states::{Pause, Screen};
 use bevy::prelude::*;
 
 pub(super) fn plugin(app: &mut App) {
     app.init_state::<Screen>();
+    app.add_sub_state::<Pause>();
     app.enable_state_scoped_entities::<Screen>();
+    app.enable_state_scoped_entities::<Pause>();
 
-    app.add_plugins(gameplay::plugin);
+    app.add_plugins((
+        title::plugin,
+        loading::plugin,
+        gameplay::plugin,
+        game_over::plugin,
+    ));
 }
@@ -0,0 +1,57 @@
+//! The first screen, starts a new run or quits the game.
+
+use bevy::prelude::*;
+
+use crate::{
+    states::Screen,
+    theme::{ButtonPressed, Widgets, ui_root},
+};
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum TitleButton {
+    Play,
+    Quit,
+}
+
+pub(super) fn plugin(app: &mut App) {
+    app.add_systems(OnEnter(Screen::Title), spawn_title_screen)
+        .add_systems(Update, handle_title_buttons.run_if(in_state(Screen::Title)));
+}
+
+fn spawn_title_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn((ui_root("Title Screen"), StateScoped(Screen::Title)))
+        .with_children(|children| {
+            children.spawn((
+                Name::new("Splash"),
+                ImageNode::new(asset_server.load("images/splash.png")),
+                Node {
+                    width: Val::Px(320.0),
+                    margin: UiRect::bottom(Val::Px(24.0)),
+                    ..default()
+                },
+            ));
+            children.button("Play").insert(TitleButton::Play);
+            children.button("Quit").insert(TitleButton::Quit);
+        });
+}
+
+fn handle_title_buttons(
+    mut presses: EventReader<ButtonPressed>,
+    button_query: Query<&TitleButton>,
+    mut next_screen: ResMut<NextState<Screen>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for ButtonPressed(entity) in presses.read() {
+        let Ok(button) = button_query.get(*entity) else {
+            continue;
+        };
+
+        match button {
+            TitleButton::Play => next_screen.set(Screen::Loading),
+            TitleButton::Quit => {
+                exit.send(AppExit::Success);
+            }
+        }
+    }
+}
@@ -6,5 +6,18 @@ pub use crate::game::turns::TurnState;
 #[derive(States, Debug, Hash, PartialEq, Eq, Clone, Default)]
 pub enum Screen {
     #[default]
+    Title,
+    Loading,
     Gameplay,
+    GameOver,
+}
+
+/// Whether the game is paused, only exists on the Gameplay screen.
+/// Pausing keeps the world around, leaving Screen::Gameplay tears it down.
+#[derive(SubStates, Debug, Hash, PartialEq, Eq, Clone, Default)]
+#[source(Screen = Screen::Gameplay)]
+pub enum Pause {
+    #[default]
+    Running,
+    Paused,
 }
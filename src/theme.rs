@@ -0,0 +1,240 @@
+//! Look and feel shared by all screens: colors, widgets, button focus and sounds.
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+
+use crate::game::input::{Action, ActionState};
+
+pub const BUTTON_BACKGROUND: Color = Color::srgb(0.18, 0.16, 0.22);
+pub const BUTTON_HOVERED_BACKGROUND: Color = Color::srgb(0.28, 0.24, 0.34);
+pub const BUTTON_PRESSED_BACKGROUND: Color = Color::srgb(0.42, 0.12, 0.12);
+pub const LABEL_TEXT: Color = Color::srgb(0.9, 0.88, 0.84);
+pub const HEADER_TEXT: Color = Color::srgb(0.8, 0.1, 0.1);
+/// dims the game behind overlays like the pause menu
+pub const OVERLAY_BACKGROUND: Color = Color::srgba(0.0, 0.0, 0.0, 0.6);
+
+pub(super) fn plugin(app: &mut App) {
+    app.init_resource::<FocusedButton>()
+        .add_event::<ButtonPressed>()
+        .add_systems(Startup, load_interaction_sounds)
+        .add_systems(
+            Update,
+            (
+                (focus_buttons, press_buttons).chain(),
+                apply_interaction_palette,
+                play_interaction_sounds,
+            ),
+        );
+}
+
+/// The button Confirm presses.
+/// Moved with MenuUp and MenuDown or by hovering a button, so menus work without a mouse.
+#[derive(Resource, Debug, Default)]
+pub struct FocusedButton(pub Option<Entity>);
+
+/// A button was clicked, or confirmed while it had focus
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ButtonPressed(pub Entity);
+
+#[derive(Resource)]
+struct InteractionSounds {
+    hover: Handle<AudioSource>,
+    press: Handle<AudioSource>,
+}
+
+/// A full screen node that centers its children in a column
+pub fn ui_root(name: impl Into<String>) -> impl Bundle {
+    (
+        Name::new(name.into()),
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(12.0),
+            ..default()
+        },
+    )
+}
+
+pub trait Widgets {
+    /// A button with a text label, it changes color and plays a sound when it is interacted with
+    fn button(&mut self, text: impl Into<String>) -> EntityCommands<'_>;
+    fn header(&mut self, text: impl Into<String>) -> EntityCommands<'_>;
+    fn label(&mut self, text: impl Into<String>) -> EntityCommands<'_>;
+}
+
+impl Widgets for ChildBuilder<'_> {
+    fn button(&mut self, text: impl Into<String>) -> EntityCommands<'_> {
+        let mut entity = self.spawn((
+            Name::new("Button"),
+            Button,
+            Node {
+                width: Val::Px(240.0),
+                height: Val::Px(56.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            BackgroundColor(BUTTON_BACKGROUND),
+        ));
+        entity.with_child((
+            Text::new(text),
+            TextFont {
+                font_size: 28.0,
+                ..default()
+            },
+            TextColor(LABEL_TEXT),
+        ));
+        entity
+    }
+
+    fn header(&mut self, text: impl Into<String>) -> EntityCommands<'_> {
+        self.spawn((
+            Name::new("Header"),
+            Text::new(text),
+            TextFont {
+                font_size: 48.0,
+                ..default()
+            },
+            TextColor(HEADER_TEXT),
+        ))
+    }
+
+    fn label(&mut self, text: impl Into<String>) -> EntityCommands<'_> {
+        self.spawn((
+            Name::new("Label"),
+            Text::new(text),
+            TextFont {
+                font_size: 24.0,
+                ..default()
+            },
+            TextColor(LABEL_TEXT),
+        ))
+    }
+}
+
+fn load_interaction_sounds(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(InteractionSounds {
+        hover: asset_server.load("audio/sound_effects/button_hover.ogg"),
+        press: asset_server.load("audio/sound_effects/button_press.ogg"),
+    });
+}
+
+/// Keep the focus on a visible button and move it between the buttons from top to bottom
+fn focus_buttons(
+    mut commands: Commands,
+    action_state: Res<ActionState>,
+    mut focused: ResMut<FocusedButton>,
+    button_query: Query<
+        (
+            Entity,
+            Ref<Interaction>,
+            &ComputedNode,
+            &GlobalTransform,
+            &InheritedVisibility,
+        ),
+        With<Button>,
+    >,
+    sounds: Res<InteractionSounds>,
+) {
+    // buttons that are not laid out yet have no position to be sorted by
+    let mut buttons: Vec<(Entity, Vec2)> = button_query
+        .iter()
+        .filter(|(_, _, node, _, visibility)| visibility.get() && node.size() != Vec2::ZERO)
+        .map(|(entity, _, _, transform, _)| (entity, transform.translation().xy()))
+        .collect();
+    buttons.sort_by(|(_, a), (_, b)| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+
+    // the mouse takes the focus when it moves onto a button
+    let hovered = button_query
+        .iter()
+        .find(|(_, interaction, ..)| interaction.is_changed() && **interaction != Interaction::None)
+        .map(|(entity, ..)| entity);
+    let current = focused
+        .0
+        .and_then(|entity| buttons.iter().position(|(button, _)| *button == entity));
+
+    let step = if action_state.triggered(Action::MenuDown) {
+        1
+    } else if action_state.triggered(Action::MenuUp) {
+        -1
+    } else {
+        0
+    };
+
+    let next = if let Some(hovered) = hovered {
+        Some(hovered)
+    } else if let Some(current) = current {
+        let index = (current as isize + step).rem_euclid(buttons.len() as isize);
+        Some(buttons[index as usize].0)
+    } else {
+        buttons.first().map(|(entity, _)| *entity)
+    };
+
+    if focused.0 != next {
+        // hovering plays its own sound
+        if step != 0 && hovered.is_none() {
+            commands.spawn((
+                AudioPlayer::new(sounds.hover.clone()),
+                PlaybackSettings::DESPAWN,
+            ));
+        }
+        focused.0 = next;
+    }
+}
+
+fn press_buttons(
+    action_state: Res<ActionState>,
+    focused: Res<FocusedButton>,
+    button_query: Query<(Entity, &Interaction), (With<Button>, Changed<Interaction>)>,
+    mut pressed: EventWriter<ButtonPressed>,
+    mut commands: Commands,
+    sounds: Res<InteractionSounds>,
+) {
+    for (entity, interaction) in button_query.iter() {
+        if *interaction == Interaction::Pressed {
+            pressed.send(ButtonPressed(entity));
+        }
+    }
+
+    if action_state.just_pressed(Action::Confirm)
+        && let Some(entity) = focused.0
+    {
+        commands.spawn((
+            AudioPlayer::new(sounds.press.clone()),
+            PlaybackSettings::DESPAWN,
+        ));
+        pressed.send(ButtonPressed(entity));
+    }
+}
+
+fn apply_interaction_palette(
+    focused: Res<FocusedButton>,
+    mut button_query: Query<(Entity, &Interaction, &mut BackgroundColor), With<Button>>,
+) {
+    for (entity, interaction, mut background) in button_query.iter_mut() {
+        let color = match interaction {
+            Interaction::Pressed => BUTTON_PRESSED_BACKGROUND,
+            _ if focused.0 == Some(entity) => BUTTON_HOVERED_BACKGROUND,
+            Interaction::Hovered => BUTTON_HOVERED_BACKGROUND,
+            Interaction::None => BUTTON_BACKGROUND,
+        };
+        background.set_if_neq(BackgroundColor(color));
+    }
+}
+
+fn play_interaction_sounds(
+    mut commands: Commands,
+    button_query: Query<&Interaction, (With<Button>, Changed<Interaction>)>,
+    sounds: Res<InteractionSounds>,
+) {
+    for interaction in button_query.iter() {
+        let sound = match interaction {
+            Interaction::None => continue,
+            Interaction::Hovered => sounds.hover.clone(),
+            Interaction::Pressed => sounds.press.clone(),
+        };
+        commands.spawn((AudioPlayer::new(sound), PlaybackSettings::DESPAWN));
+    }
+}
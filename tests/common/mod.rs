@@ -0,0 +1,43 @@
+//! Helpers shared by the integration tests, they run the game headlessly with the SimulationPlugin.
+
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::Duration,
+};
+
+use bevy::prelude::*;
+use m_rouge::{
+    SimulationPlugin,
+    game::{save::SavePath, tiles::TileRegistry},
+};
+
+/// Add the SimulationPlugin and update the app until the tile definitions finished loading.
+/// Saves go to the temp directory unless the test set its own SavePath.
+pub fn start_simulation(app: &mut App) {
+    if !app.world().contains_resource::<SavePath>() {
+        app.insert_resource(SavePath(temp_path("quicksave.ron")));
+    }
+    app.add_plugins(SimulationPlugin);
+    app.finish();
+    app.cleanup();
+
+    for _ in 0..500 {
+        app.update();
+        if app.world().contains_resource::<TileRegistry>() {
+            // one more update to spawn the chunks around the player
+            app.update();
+            return;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    panic!("tile definitions did not load");
+}
+
+/// A path in the temp directory that no other test uses
+pub fn temp_path(name: &str) -> PathBuf {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("m-rouge-{}-{id}-{name}", std::process::id()))
+}
@@ -0,0 +1,122 @@
+mod common;
+
+use std::fs;
+
+use bevy::prelude::*;
+use m_rouge::{
+    components::TurnTaker,
+    game::{
+        chunk_store::{ActorKind, ChunkManager, StoredActor, StoredChunk},
+        combat::PlayerDied,
+        devil::Devil,
+        map::{CHUNK_SIZE, GameGrid},
+        save::{LoadGame, Permadeath, SaveFile, SaveGame, SavePath},
+        tiles::TileRegistry,
+    },
+};
+
+#[test]
+fn save_round_trip_keeps_more_chunks_than_the_memory_budget() {
+    let save_path = common::temp_path("quicksave.ron");
+    let spill_dir = common::temp_path("chunks");
+    let mut chunk_manager = ChunkManager::default();
+    chunk_manager.spill_dir = Some(spill_dir.clone());
+    let mut app = App::new();
+    app.insert_resource(SavePath(save_path.clone()))
+        .insert_resource(chunk_manager);
+    common::start_simulation(&mut app);
+
+    let wall = app.world().resource::<TileRegistry>().kind("wall").unwrap();
+    let mut chunk_manager = app.world_mut().resource_mut::<ChunkManager>();
+    // far away from the player, so none of them is spawned again
+    let positions: Vec<IVec2> = (0..chunk_manager.memory_budget as i32 + 16)
+        .map(|x| IVec2::new(1000 + x, 1000))
+        .collect();
+    for pos in &positions {
+        // solid walls differ from every generated chunk, so the save has to keep the tiles
+        chunk_manager.store(
+            *pos,
+            StoredChunk {
+                tiles: vec![wall; (CHUNK_SIZE.x * CHUNK_SIZE.y) as usize],
+                actors: Vec::new(),
+            },
+        );
+    }
+    assert!(
+        spill_dir.exists(),
+        "chunks over the budget were not spilled"
+    );
+
+    app.world_mut().send_event(SaveGame);
+    app.update();
+    app.world_mut().send_event(LoadGame);
+    app.update();
+
+    let stored: Vec<(IVec2, StoredChunk)> = app.world().resource::<ChunkManager>().stored_chunks();
+    for pos in &positions {
+        let (_, chunk) = stored
+            .iter()
+            .find(|(stored_pos, _)| stored_pos == pos)
+            .unwrap_or_else(|| panic!("chunk {pos} was lost"));
+        assert!(chunk.tiles.iter().all(|kind| *kind == wall));
+    }
+
+    app.world_mut().resource_mut::<ChunkManager>().clear();
+    let _ = fs::remove_file(save_path);
+    let _ = fs::remove_dir_all(spill_dir);
+}
+
+#[test]
+fn only_permadeath_deletes_the_save_on_death() {
+    let mut app = App::new();
+    common::start_simulation(&mut app);
+    let save_path = app.world().resource::<SavePath>().0.clone();
+
+    app.world_mut().send_event(SaveGame);
+    app.update();
+    assert!(save_path.exists());
+
+    app.world_mut().send_event(PlayerDied);
+    app.update();
+    assert!(save_path.exists());
+
+    app.insert_resource(Permadeath(true));
+    app.world_mut().send_event(PlayerDied);
+    app.update();
+    assert!(!save_path.exists());
+}
+
+#[test]
+fn loading_next_to_a_saved_devil_keeps_it() {
+    let mut app = App::new();
+    common::start_simulation(&mut app);
+    let save_path = app.world().resource::<SavePath>().0.clone();
+
+    app.world_mut().send_event(SaveGame);
+    app.update();
+
+    // put a devil right where the player stands
+    let mut save = SaveFile::read(&save_path).unwrap();
+    let player_pos = save.player.pos;
+    let player_chunk = GameGrid::grid_pos_to_chunk_pos(player_pos);
+    let chunk = save
+        .chunks
+        .iter_mut()
+        .find(|chunk| chunk.pos == player_chunk)
+        .unwrap();
+    chunk.actors.push(StoredActor {
+        kind: ActorKind::Devil,
+        pos: player_pos,
+        turn_taker: TurnTaker::default(),
+        health: None,
+    });
+    let saved_devils: usize = save.chunks.iter().map(|chunk| chunk.actors.len()).sum();
+    save.write(&save_path).unwrap();
+
+    app.world_mut().send_event(LoadGame);
+    app.update();
+
+    let mut devils = app.world_mut().query_filtered::<Entity, With<Devil>>();
+    assert_eq!(devils.iter(app.world()).count(), saved_devils);
+    let _ = fs::remove_file(save_path);
+}
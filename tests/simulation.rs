@@ -0,0 +1,61 @@
+mod common;
+
+use bevy::prelude::*;
+use m_rouge::{
+    components::TurnTaker,
+    game::{
+        devil::Devil,
+        map_generator::{MapGeneratorKind, SelectedGenerator},
+        player::PlayerAction,
+        turns::TurnQueue,
+    },
+    states::TurnState,
+};
+
+const TURNS: u64 = 10;
+
+#[test]
+fn waiting_advances_the_turns() {
+    let mut app = App::new();
+    common::start_simulation(&mut app);
+    assert_eq!(app.world().resource::<TurnQueue>().turn(), 0);
+
+    let mut environment_turns = 0;
+    for _ in 0..1000 {
+        if app.world().resource::<TurnQueue>().turn() >= TURNS {
+            break;
+        }
+
+        match app.world().resource::<State<TurnState>>().get() {
+            TurnState::Player => {
+                app.world_mut().send_event(PlayerAction::Wait);
+            }
+            TurnState::Environment => environment_turns += 1,
+        }
+        app.update();
+    }
+
+    assert!(app.world().resource::<TurnQueue>().turn() >= TURNS);
+    // everyone else got to act in between
+    assert!(environment_turns > 0);
+    let time = app.world().resource::<TurnQueue>().time();
+    let mut devils = app.world_mut().query_filtered::<&TurnTaker, With<Devil>>();
+    assert!(devils.iter(app.world()).count() > 0);
+    assert!(
+        devils
+            .iter(app.world())
+            .all(|turn_taker| turn_taker.next_turn >= time)
+    );
+}
+
+#[test]
+fn the_generator_can_be_chosen() {
+    let mut app = App::new();
+    app.insert_resource(SelectedGenerator(MapGeneratorKind::Bsp));
+    common::start_simulation(&mut app);
+
+    assert_eq!(
+        *app.world().resource::<MapGeneratorKind>(),
+        MapGeneratorKind::Bsp
+    );
+}